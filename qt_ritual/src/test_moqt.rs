@@ -69,7 +69,7 @@ fn build_moqt() -> Result<TempTestDir> {
 
     let repo_template_dir = repo_dir_path("qt_ritual/crate_templates")?;
     let add_env = |name, path: &Path| -> Result<()> {
-        let value = add_env_path_item(name, vec![path.to_path_buf()])?;
+        let value = add_env_path_item(name, vec![path.to_path_buf()], true)?;
         env::set_var(name, value);
         Ok(())
     };
@@ -5,10 +5,11 @@ pub use crate::cpp_operator::CppOperator;
 use crate::cpp_type::{CppTemplateParameter, CppType};
 use crate::database::DatabaseClient;
 use itertools::Itertools;
-use ritual_common::errors::{bail, ensure, Error, Result};
+use ritual_common::errors::{bail, ensure, Error, Result, ResultExt};
 use ritual_common::utils::MapIfOk;
 use serde_derive::{Deserialize, Serialize};
 use std::fmt;
+use std::path::Path;
 use std::str::FromStr;
 
 /// One item of a C++ enum declaration
@@ -111,6 +112,67 @@ pub struct CppOriginLocation {
     pub column: u32,
 }
 
+/// Name and location of a C++ declaration used to describe errors
+/// that occur while processing it.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct CppOrigin {
+    /// Full name of the declaration, e.g. `QString::toHtml`.
+    pub name: String,
+    /// Name of the header file containing the declaration.
+    pub header: Option<String>,
+    /// Line of the declaration in the header file.
+    pub line: Option<u32>,
+}
+
+impl CppOrigin {
+    /// Creates an origin without a location.
+    pub fn new(name: impl Into<String>) -> Self {
+        CppOrigin {
+            name: name.into(),
+            header: None,
+            line: None,
+        }
+    }
+
+    /// Creates an origin of a declaration parsed at `location`.
+    pub fn with_location(name: impl Into<String>, location: &CppOriginLocation) -> Self {
+        let header = Path::new(&location.include_file_path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(&location.include_file_path);
+        CppOrigin {
+            name: name.into(),
+            header: Some(header.to_string()),
+            line: Some(location.line),
+        }
+    }
+}
+
+impl fmt::Display for CppOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        match (&self.header, self.line) {
+            (Some(header), Some(line)) => write!(f, " ({}:{})", header, line),
+            (Some(header), None) => write!(f, " ({})", header),
+            (None, Some(line)) => write!(f, " (line {})", line),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Adds the C++ declaration being processed to the error context.
+pub trait CppOriginResultExt<T> {
+    /// If `self` is an error, wraps it in a context
+    /// `while processing <name> (<header>:<line>)`.
+    fn with_cpp_origin(self, origin: &CppOrigin) -> Result<T>;
+}
+
+impl<T> CppOriginResultExt<T> for Result<T> {
+    fn with_cpp_origin(self, origin: &CppOrigin) -> Result<T> {
+        Ok(self.with_context(|_| format!("while processing {}", origin))?)
+    }
+}
+
 /// Visibility of a C++ entity. Defaults to `Public`
 /// for entities that can't have visibility (like free functions)
 #[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
@@ -2,7 +2,7 @@
 
 use crate::cpp_data::{CppPath, CppPathItem, CppVisibility};
 use crate::cpp_ffi_data::CppCast;
-pub use crate::cpp_operator::{CppOperator, CppOperatorInfo};
+pub use crate::cpp_operator::{Associativity, CppOperator, CppOperatorInfo, RustOperatorMapping};
 use crate::cpp_type::{CppPointerLikeTypeKind, CppType};
 use crate::rust_info::RustQtReceiverType;
 use itertools::Itertools;
@@ -44,6 +44,8 @@ pub enum CppOperator {
     GreaterThanOrEqualTo,
     /// a <= b
     LessThanOrEqualTo,
+    /// a <=> b
+    ThreeWayComparison,
     /// !a
     LogicalNot,
     /// a && b
@@ -107,6 +109,28 @@ pub enum CppOperator {
     DeleteArray,
 }
 
+/// Associativity of a C++ operator
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Associativity {
+    /// `a @ b @ c` is parsed as `(a @ b) @ c`
+    Left,
+    /// `a @ b @ c` is parsed as `a @ (b @ c)`
+    Right,
+}
+
+/// Representation of a C++ operator in the generated Rust API
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum RustOperatorMapping {
+    /// The operator is implemented as `method` of the Rust trait `trait_path`.
+    Trait {
+        trait_path: &'static str,
+        method: &'static str,
+    },
+    /// Rust has no such operator, so the operator is exposed as
+    /// an inherent method with this name.
+    InherentMethod(&'static str),
+}
+
 /// Constraints applied to a C++ operator method
 /// of a certain kind
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -124,10 +148,17 @@ pub struct CppOperatorInfo {
     /// True if this kind of operator can have variadic arguments.
     /// Only the function call operator has this property.
     pub allows_variadic_arguments: bool,
+    /// True if this kind of operator can only be overloaded as
+    /// a non-static class member function (e.g. `operator=` and `operator[]`).
+    pub member_only: bool,
 }
 
 impl CppOperator {
-    /// Reports information about this operator
+    /// Reports information about this operator.
+    ///
+    /// Note that `ThreeWayComparison` (`operator<=>`) corresponds to
+    /// Rust's `Ord::cmp` and `PartialOrd::partial_cmp` rather than to any
+    /// single comparison operator.
     pub fn info(&self) -> CppOperatorInfo {
         use self::CppOperator::*;
 
@@ -136,6 +167,14 @@ impl CppOperator {
                 function_name_suffix: Some(suffix),
                 arguments_count: count,
                 allows_variadic_arguments: false,
+                member_only: false,
+            }
+        }
+
+        fn member_oi(suffix: &'static str, count: usize) -> CppOperatorInfo {
+            CppOperatorInfo {
+                member_only: true,
+                ..oi(suffix, count)
             }
         }
 
@@ -144,8 +183,9 @@ impl CppOperator {
                 function_name_suffix: None,
                 arguments_count: 1,
                 allows_variadic_arguments: false,
+                member_only: true,
             },
-            Assignment => oi("=", 2),
+            Assignment => member_oi("=", 2),
             Addition => oi("+", 2),
             Subtraction => oi("-", 2),
             UnaryPlus => oi("+", 1),
@@ -163,6 +203,7 @@ impl CppOperator {
             LessThan => oi("<", 2),
             GreaterThanOrEqualTo => oi(">=", 2),
             LessThanOrEqualTo => oi("<=", 2),
+            ThreeWayComparison => oi("<=>", 2),
             LogicalNot => oi("!", 1),
             LogicalAnd => oi("&&", 2),
             LogicalOr => oi("||", 2),
@@ -182,15 +223,16 @@ impl CppOperator {
             BitwiseXorAssignment => oi("^=", 2),
             BitwiseLeftShiftAssignment => oi("<<=", 2),
             BitwiseRightShiftAssignment => oi(">>=", 2),
-            Subscript => oi("[]", 2),
+            Subscript => member_oi("[]", 2),
             Indirection => oi("*", 1),
             AddressOf => oi("&", 1),
-            StructureDereference => oi("->", 1),
+            StructureDereference => member_oi("->", 1),
             PointerToMember => oi("->*", 2),
             FunctionCall => CppOperatorInfo {
                 function_name_suffix: Some("()"),
                 arguments_count: 0,
                 allows_variadic_arguments: true,
+                member_only: true,
             },
             Comma => oi(",", 2),
             New => oi("new", 2),
@@ -200,6 +242,311 @@ impl CppOperator {
         }
     }
 
+    /// Returns a name fragment for this operator that is a valid C identifier.
+    /// The fragment is unique for each operator kind, so it can be used
+    /// to construct names of FFI functions and Rust wrapper functions.
+    pub fn c_name(&self) -> &'static str {
+        use self::CppOperator::*;
+
+        match *self {
+            Conversion(..) => "convert",
+            Assignment => "set_from",
+            Addition => "add",
+            Subtraction => "sub",
+            UnaryPlus => "unary_plus",
+            UnaryMinus => "neg",
+            Multiplication => "mul",
+            Division => "div",
+            Modulo => "rem",
+            PrefixIncrement => "inc",
+            PostfixIncrement => "inc_postfix",
+            PrefixDecrement => "dec",
+            PostfixDecrement => "dec_postfix",
+            EqualTo => "eq",
+            NotEqualTo => "ne",
+            GreaterThan => "gt",
+            LessThan => "lt",
+            GreaterThanOrEqualTo => "ge",
+            LessThanOrEqualTo => "le",
+            ThreeWayComparison => "cmp",
+            LogicalNot => "not",
+            LogicalAnd => "and",
+            LogicalOr => "or",
+            BitwiseNot => "bit_not",
+            BitwiseAnd => "bit_and",
+            BitwiseOr => "bit_or",
+            BitwiseXor => "bit_xor",
+            BitwiseLeftShift => "shl",
+            BitwiseRightShift => "shr",
+            AdditionAssignment => "add_assign",
+            SubtractionAssignment => "sub_assign",
+            MultiplicationAssignment => "mul_assign",
+            DivisionAssignment => "div_assign",
+            ModuloAssignment => "rem_assign",
+            BitwiseAndAssignment => "bit_and_assign",
+            BitwiseOrAssignment => "bit_or_assign",
+            BitwiseXorAssignment => "bit_xor_assign",
+            BitwiseLeftShiftAssignment => "shl_assign",
+            BitwiseRightShiftAssignment => "shr_assign",
+            Subscript => "index",
+            Indirection => "indirection",
+            AddressOf => "address_of",
+            StructureDereference => "struct_deref",
+            PointerToMember => "ptr_to_member",
+            FunctionCall => "call",
+            Comma => "comma",
+            New => "new",
+            NewArray => "new_array",
+            Delete => "delete",
+            DeleteArray => "delete_array",
+        }
+    }
+
+    /// Returns true if this is an arithmetic operator, including
+    /// increment and decrement operators.
+    pub fn is_arithmetic(&self) -> bool {
+        use self::CppOperator::*;
+
+        match *self {
+            Addition | Subtraction | UnaryPlus | UnaryMinus | Multiplication | Division
+            | Modulo | PrefixIncrement | PostfixIncrement | PrefixDecrement | PostfixDecrement => {
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if this is a comparison operator.
+    pub fn is_comparison(&self) -> bool {
+        use self::CppOperator::*;
+
+        match *self {
+            EqualTo | NotEqualTo | GreaterThan | LessThan | GreaterThanOrEqualTo
+            | LessThanOrEqualTo | ThreeWayComparison => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if this is a logical operator.
+    pub fn is_logical(&self) -> bool {
+        use self::CppOperator::*;
+
+        match *self {
+            LogicalNot | LogicalAnd | LogicalOr => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if this is a bitwise operator (not including
+    /// compound assignment operators).
+    pub fn is_bitwise(&self) -> bool {
+        use self::CppOperator::*;
+
+        match *self {
+            BitwiseNot | BitwiseAnd | BitwiseOr | BitwiseXor | BitwiseLeftShift
+            | BitwiseRightShift => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if this is the assignment operator or
+    /// a compound assignment operator (e.g. `+=`).
+    pub fn is_assignment(&self) -> bool {
+        use self::CppOperator::*;
+
+        match *self {
+            Assignment
+            | AdditionAssignment
+            | SubtractionAssignment
+            | MultiplicationAssignment
+            | DivisionAssignment
+            | ModuloAssignment
+            | BitwiseAndAssignment
+            | BitwiseOrAssignment
+            | BitwiseXorAssignment
+            | BitwiseLeftShiftAssignment
+            | BitwiseRightShiftAssignment => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if this operator is applied to a single operand.
+    /// Postfix increment and decrement are considered unary even though
+    /// they accept an additional dummy `int` argument in C++.
+    pub fn is_unary(&self) -> bool {
+        use self::CppOperator::*;
+
+        match *self {
+            UnaryPlus | UnaryMinus | PrefixIncrement | PostfixIncrement | PrefixDecrement
+            | PostfixDecrement | LogicalNot | BitwiseNot | Indirection | AddressOf => true,
+            _ => false,
+        }
+    }
+
+    /// Returns path and method name of the Rust trait corresponding to this operator,
+    /// or `None` if there is no such trait.
+    fn rust_trait_and_method(&self) -> Option<(&'static str, &'static str)> {
+        use self::CppOperator::*;
+
+        Some(match *self {
+            Addition => ("std::ops::Add", "add"),
+            Subtraction => ("std::ops::Sub", "sub"),
+            Multiplication => ("std::ops::Mul", "mul"),
+            Division => ("std::ops::Div", "div"),
+            Modulo => ("std::ops::Rem", "rem"),
+            UnaryMinus => ("std::ops::Neg", "neg"),
+            LogicalNot | BitwiseNot => ("std::ops::Not", "not"),
+            BitwiseAnd => ("std::ops::BitAnd", "bitand"),
+            BitwiseOr => ("std::ops::BitOr", "bitor"),
+            BitwiseXor => ("std::ops::BitXor", "bitxor"),
+            BitwiseLeftShift => ("std::ops::Shl", "shl"),
+            BitwiseRightShift => ("std::ops::Shr", "shr"),
+            AdditionAssignment => ("std::ops::AddAssign", "add_assign"),
+            SubtractionAssignment => ("std::ops::SubAssign", "sub_assign"),
+            MultiplicationAssignment => ("std::ops::MulAssign", "mul_assign"),
+            DivisionAssignment => ("std::ops::DivAssign", "div_assign"),
+            ModuloAssignment => ("std::ops::RemAssign", "rem_assign"),
+            BitwiseAndAssignment => ("std::ops::BitAndAssign", "bitand_assign"),
+            BitwiseOrAssignment => ("std::ops::BitOrAssign", "bitor_assign"),
+            BitwiseXorAssignment => ("std::ops::BitXorAssign", "bitxor_assign"),
+            BitwiseLeftShiftAssignment => ("std::ops::ShlAssign", "shl_assign"),
+            BitwiseRightShiftAssignment => ("std::ops::ShrAssign", "shr_assign"),
+            Subscript => ("std::ops::Index", "index"),
+            Indirection => ("std::ops::Deref", "deref"),
+            EqualTo => ("std::cmp::PartialEq", "eq"),
+            NotEqualTo => ("std::cmp::PartialEq", "ne"),
+            GreaterThan => ("std::cmp::PartialOrd", "gt"),
+            LessThan => ("std::cmp::PartialOrd", "lt"),
+            GreaterThanOrEqualTo => ("std::cmp::PartialOrd", "ge"),
+            LessThanOrEqualTo => ("std::cmp::PartialOrd", "le"),
+            ThreeWayComparison => ("std::cmp::PartialOrd", "partial_cmp"),
+            Conversion(..) | Assignment | UnaryPlus | PrefixIncrement | PostfixIncrement
+            | PrefixDecrement | PostfixDecrement | LogicalAnd | LogicalOr | AddressOf
+            | StructureDereference | PointerToMember | FunctionCall | Comma | New | NewArray
+            | Delete | DeleteArray => return None,
+        })
+    }
+
+    /// Returns full path of the Rust standard library trait corresponding to
+    /// this operator (e.g. `"std::ops::Add"` for `Addition`), or `None` if
+    /// the operator has no Rust equivalent.
+    pub fn rust_trait(&self) -> Option<&'static str> {
+        self.rust_trait_and_method().map(|(t, _)| t)
+    }
+
+    /// Returns name of the method of the trait returned by `rust_trait()`
+    /// that corresponds to this operator (e.g. `"add"` for `Addition`).
+    pub fn rust_trait_method(&self) -> Option<&'static str> {
+        self.rust_trait_and_method().map(|(_, m)| m)
+    }
+
+    /// Returns how this operator should be represented in the Rust API,
+    /// or `None` if it has no Rust representation.
+    ///
+    /// Increment and decrement operators are mapped to inherent methods
+    /// (`inc`, `inc_postfix`, `dec` and `dec_postfix`) because Rust
+    /// has no corresponding operators.
+    pub fn rust_mapping(&self) -> Option<RustOperatorMapping> {
+        use self::CppOperator::*;
+
+        let method = match *self {
+            PrefixIncrement => "inc",
+            PostfixIncrement => "inc_postfix",
+            PrefixDecrement => "dec",
+            PostfixDecrement => "dec_postfix",
+            _ => {
+                return self
+                    .rust_trait_and_method()
+                    .map(|(trait_path, method)| RustOperatorMapping::Trait { trait_path, method });
+            }
+        };
+        Some(RustOperatorMapping::InherentMethod(method))
+    }
+
+    /// Parses operator from its C++ function name (e.g. `"operator+"`
+    /// or `"operator new[]"`). Returns `None` if `name` is not a name
+    /// of an operator function.
+    ///
+    /// Some operators share the same spelling and can only be distinguished
+    /// by arity (`+`, `-`, `*`, `&`, `++`, `--`). In this case the operator
+    /// that comes first in `CppOperator::all()` is returned (the binary form for
+    /// `+`, `-`, `*`, `&` and the prefix form for `++` and `--`), and the caller
+    /// must disambiguate it using the number of arguments.
+    ///
+    /// Conversion operators (e.g. `"operator int"`) are not recognized
+    /// by this function because their names contain a C++ type.
+    pub fn from_cpp_name(name: &str) -> Option<CppOperator> {
+        if !name.starts_with("operator") {
+            return None;
+        }
+        let rest = &name["operator".len()..];
+        if rest
+            .chars()
+            .next()
+            .map_or(true, |c| c.is_alphanumeric() || c == '_')
+        {
+            // either nothing follows "operator" or it's a different identifier
+            return None;
+        }
+        let suffix = rest
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>();
+        CppOperator::all()
+            .into_iter()
+            .find(|operator| operator.info().function_name_suffix == Some(suffix.as_str()))
+    }
+
+    /// Returns precedence of this operator according to the C++ operator
+    /// precedence table. Operators with higher values bind tighter
+    /// (e.g. `Multiplication` has higher precedence than `Addition`).
+    pub fn precedence(&self) -> u8 {
+        use self::CppOperator::*;
+
+        match *self {
+            PostfixIncrement | PostfixDecrement | FunctionCall | Subscript
+            | StructureDereference => 16,
+            PrefixIncrement | PrefixDecrement | UnaryPlus | UnaryMinus | LogicalNot
+            | BitwiseNot | Conversion(..) | Indirection | AddressOf | New | NewArray | Delete
+            | DeleteArray => 15,
+            PointerToMember => 14,
+            Multiplication | Division | Modulo => 13,
+            Addition | Subtraction => 12,
+            BitwiseLeftShift | BitwiseRightShift => 11,
+            ThreeWayComparison => 10,
+            LessThan | LessThanOrEqualTo | GreaterThan | GreaterThanOrEqualTo => 9,
+            EqualTo | NotEqualTo => 8,
+            BitwiseAnd => 7,
+            BitwiseXor => 6,
+            BitwiseOr => 5,
+            LogicalAnd => 4,
+            LogicalOr => 3,
+            Assignment
+            | AdditionAssignment
+            | SubtractionAssignment
+            | MultiplicationAssignment
+            | DivisionAssignment
+            | ModuloAssignment
+            | BitwiseAndAssignment
+            | BitwiseOrAssignment
+            | BitwiseXorAssignment
+            | BitwiseLeftShiftAssignment
+            | BitwiseRightShiftAssignment => 2,
+            Comma => 1,
+        }
+    }
+
+    /// Returns associativity of this operator. Prefix unary operators and
+    /// assignment operators are right-associative, all other operators are
+    /// left-associative.
+    pub fn associativity(&self) -> Associativity {
+        if self.precedence() == 15 || self.is_assignment() {
+            Associativity::Right
+        } else {
+            Associativity::Left
+        }
+    }
+
     /// Returns all existing operator kinds except for
     /// conversion operator which includes an arbitrary C++ type.
     pub fn all() -> Vec<CppOperator> {
@@ -223,6 +570,7 @@ impl CppOperator {
             LessThan,
             GreaterThanOrEqualTo,
             LessThanOrEqualTo,
+            ThreeWayComparison,
             LogicalNot,
             LogicalAnd,
             LogicalOr,
@@ -36,81 +36,13 @@ fn wrap_unsafe(in_unsafe_context: bool, content: &str) -> String {
 }
 
 pub fn rust_common_type_to_code(rust_type: &RustCommonType, current_crate: Option<&str>) -> String {
-    let mut code = rust_type.path.full_name(current_crate);
-    if let Some(args) = &rust_type.generic_arguments {
-        write!(
-            code,
-            "<{}>",
-            args.iter()
-                .map(|x| rust_type_to_code(x, current_crate))
-                .join(", ",)
-        )
-        .unwrap();
-    }
-    code
+    rust_type.to_rust_code(current_crate)
 }
 
 /// Generates Rust code representing type `rust_type` inside crate `crate_name`.
 /// Same as `RustCodeGenerator::rust_type_to_code`, but accessible by other modules.
 pub fn rust_type_to_code(rust_type: &RustType, current_crate: Option<&str>) -> String {
-    match rust_type {
-        RustType::Tuple(types) => {
-            let types_text = types
-                .iter()
-                .map(|t| rust_type_to_code(t, current_crate) + ",")
-                .join("");
-            format!("({})", types_text)
-        }
-        RustType::Primitive(type1) | RustType::GenericParameter(type1) => type1.to_string(),
-        RustType::PointerLike {
-            kind,
-            target,
-            is_const,
-        } => {
-            let target_code = rust_type_to_code(&*target, current_crate);
-            match kind {
-                RustPointerLikeTypeKind::Pointer => {
-                    if *is_const {
-                        format!("*const {}", target_code)
-                    } else {
-                        format!("*mut {}", target_code)
-                    }
-                }
-                RustPointerLikeTypeKind::Reference { lifetime } => {
-                    let lifetime_text = match lifetime {
-                        Some(lifetime) => format!("'{} ", lifetime),
-                        None => String::new(),
-                    };
-                    if *is_const {
-                        format!("&{}{}", lifetime_text, target_code)
-                    } else {
-                        format!("&{}mut {}", lifetime_text, target_code)
-                    }
-                }
-            }
-        }
-        RustType::Common(common) => rust_common_type_to_code(common, current_crate),
-        RustType::FunctionPointer(function) => format!(
-            "extern \"C\" fn({}){}",
-            function
-                .arguments
-                .iter()
-                .map(|arg| rust_type_to_code(arg, current_crate))
-                .join(", "),
-            if function.return_type.is_unit() {
-                String::new()
-            } else {
-                format!(
-                    " -> {}",
-                    rust_type_to_code(&function.return_type, current_crate)
-                )
-            }
-        ),
-        RustType::ImplTrait(trait_type) => format!(
-            "impl {}",
-            rust_common_type_to_code(trait_type, current_crate)
-        ),
-    }
+    rust_type.to_rust_code(current_crate)
 }
 
 struct Generator<'a> {
@@ -687,6 +619,10 @@ impl Generator<'_> {
                 )?;
                 format!("&{}", expr)
             }
+            RustToFfiTypeConversion::BoolToInt => format!("{} != 0", source_expr),
+            RustToFfiTypeConversion::CStrToPtr => {
+                bail!("CStrToPtr is not convertable from FFI type");
+            }
             RustToFfiTypeConversion::ImplCastInto(_) => {
                 bail!("ImplCastInto is not convertable from FFI type");
             }
@@ -756,9 +692,21 @@ impl Generator<'_> {
             RustToFfiTypeConversion::UnitToAnything => {
                 bail!("UnitToAnything is not possible to use in argument position");
             }
-            RustToFfiTypeConversion::AsCast { .. } => {
+            RustToFfiTypeConversion::AsCast { .. } | RustToFfiTypeConversion::BoolToInt => {
                 format!("{} as {}", expr, self.rust_type_to_code(type1.ffi_type()))
             }
+            RustToFfiTypeConversion::CStrToPtr => {
+                let call = format!(
+                    "::std::ffi::CString::new({}).expect(\"string contains \
+                     an interior nul byte\").as_ptr()",
+                    expr
+                );
+                if type1.ffi_type().is_const_pointer_like()? {
+                    call
+                } else {
+                    format!("{} as {}", call, self.rust_type_to_code(type1.ffi_type()))
+                }
+            }
             RustToFfiTypeConversion::RefTo(conversion) => {
                 let intermediate =
                     RustFinalType::new(type1.ffi_type().clone(), (**conversion).clone())?;
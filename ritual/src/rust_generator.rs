@@ -25,8 +25,8 @@ use crate::rust_info::{
     RustWrapperTypeKind, UnnamedRustFunction,
 };
 use crate::rust_type::{
-    RustClosureToCallbackConversion, RustCommonType, RustFinalType, RustFunctionPointerType,
-    RustPath, RustPointerLikeTypeKind, RustToFfiTypeConversion, RustType,
+    sanitize_rust_identifier, RustClosureToCallbackConversion, RustCommonType, RustFinalType,
+    RustFunctionPointerType, RustPath, RustPointerLikeTypeKind, RustToFfiTypeConversion, RustType,
 };
 use itertools::Itertools;
 use log::{debug, trace};
@@ -45,33 +45,6 @@ pub fn qt_core_path(crate_name: &str) -> RustPath {
     }
 }
 
-/// Adds "_" to a string if it is a reserved word in Rust
-fn sanitize_rust_identifier(name: &str, is_module: bool) -> String {
-    match name {
-        "abstract" | "alignof" | "as" | "become" | "box" | "break" | "const" | "continue"
-        | "crate" | "do" | "else" | "enum" | "extern" | "false" | "final" | "fn" | "for" | "if"
-        | "impl" | "in" | "let" | "loop" | "macro" | "match" | "mod" | "move" | "mut"
-        | "offsetof" | "override" | "priv" | "proc" | "pub" | "pure" | "ref" | "return"
-        | "Self" | "self" | "sizeof" | "static" | "struct" | "super" | "trait" | "true"
-        | "type" | "typeof" | "unsafe" | "unsized" | "use" | "virtual" | "where" | "while"
-        | "yield" => format!("{}_", name),
-        "lib" | "main" if is_module => format!("{}_", name),
-        _ => name.to_string(),
-    }
-}
-
-#[test]
-fn sanitize_rust_identifier_test() {
-    assert_eq!(&sanitize_rust_identifier("good", false), "good");
-    assert_eq!(&sanitize_rust_identifier("Self", false), "Self_");
-    assert_eq!(&sanitize_rust_identifier("mod", false), "mod_");
-    assert_eq!(&sanitize_rust_identifier("mod", true), "mod_");
-    assert_eq!(&sanitize_rust_identifier("main", false), "main");
-    assert_eq!(&sanitize_rust_identifier("main", true), "main_");
-    assert_eq!(&sanitize_rust_identifier("lib", false), "lib");
-    assert_eq!(&sanitize_rust_identifier("lib", true), "lib_");
-}
-
 #[derive(Debug)]
 struct FunctionWithDesiredPath {
     function: UnnamedRustFunction,
@@ -355,6 +328,7 @@ impl TraitImplInfo {
             | CppOperator::PostfixIncrement
             | CppOperator::PostfixDecrement
             | CppOperator::NotEqualTo
+            | CppOperator::ThreeWayComparison
             | CppOperator::LogicalAnd
             | CppOperator::LogicalOr
             | CppOperator::BitwiseNot
@@ -2573,60 +2547,10 @@ impl FunctionWithDesiredPath {
 /// Returns alphanumeric identifier for this operator
 /// used to name wrapper functions.
 fn operator_function_name(operator: &CppOperator) -> Result<&'static str> {
-    use self::CppOperator::*;
-    Ok(match operator {
-        Conversion(..) => {
-            bail!("operator_function_name: conversion operators are not supported");
-        }
-        Assignment => "set_from",
-        Addition => "add",
-        Subtraction => "sub",
-        UnaryPlus => "unary_plus",
-        UnaryMinus => "neg",
-        Multiplication => "mul",
-        Division => "div",
-        Modulo => "rem",
-        PrefixIncrement => "inc",
-        PostfixIncrement => "inc_postfix",
-        PrefixDecrement => "dec",
-        PostfixDecrement => "dec_postfix",
-        EqualTo => "eq",
-        NotEqualTo => "ne",
-        GreaterThan => "gt",
-        LessThan => "lt",
-        GreaterThanOrEqualTo => "ge",
-        LessThanOrEqualTo => "le",
-        LogicalNot => "not",
-        LogicalAnd => "and",
-        LogicalOr => "or",
-        BitwiseNot => "bit_not",
-        BitwiseAnd => "bit_and",
-        BitwiseOr => "bit_or",
-        BitwiseXor => "bit_xor",
-        BitwiseLeftShift => "shl",
-        BitwiseRightShift => "shr",
-        AdditionAssignment => "add_assign",
-        SubtractionAssignment => "sub_assign",
-        MultiplicationAssignment => "mul_assign",
-        DivisionAssignment => "div_assign",
-        ModuloAssignment => "rem_assign",
-        BitwiseAndAssignment => "bit_and_assign",
-        BitwiseOrAssignment => "bit_or_assign",
-        BitwiseXorAssignment => "bit_xor_assign",
-        BitwiseLeftShiftAssignment => "shl_assign",
-        BitwiseRightShiftAssignment => "shr_assign",
-        Subscript => "index",
-        Indirection => "indirection",
-        AddressOf => "address_of",
-        StructureDereference => "struct_deref",
-        PointerToMember => "ptr_to_member",
-        FunctionCall => "call",
-        Comma => "comma",
-        New => "new",
-        NewArray => "new_array",
-        Delete => "delete",
-        DeleteArray => "delete_array",
-    })
+    if let CppOperator::Conversion(..) = operator {
+        bail!("operator_function_name: conversion operators are not supported");
+    }
+    Ok(operator.c_name())
 }
 
 #[allow(dead_code)]
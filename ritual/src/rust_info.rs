@@ -2,14 +2,19 @@
 
 use crate::cpp_data::CppPath;
 use crate::cpp_ffi_data::CppFfiFunction;
+use crate::cpp_operator::CppOperator;
 use crate::cpp_type::CppType;
 use crate::database::DbItem;
-use crate::rust_code_generator::{rust_common_type_to_code, rust_type_to_code};
 use crate::rust_type::{
-    RustCommonType, RustFinalType, RustPath, RustPointerLikeTypeKind, RustType,
+    can_elide_lifetimes, can_elide_method_lifetimes, sanitize_rust_identifier, RustCommonType,
+    RustFinalType, RustPath, RustPointerLikeTypeKind, RustType,
 };
+use itertools::Itertools;
 use ritual_common::errors::{bail, Result};
+use ritual_common::utils::{dylib_prefix, dylib_suffix, staticlib_suffix, MapIfOk};
 use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::iter;
 
 /// One variant of a Rust enum
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -19,6 +24,76 @@ pub struct RustEnumValue {
     pub value: i64,
 }
 
+/// Information about a Rust enum generated from a C++ enum
+/// with explicit discriminants.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct RustEnum {
+    pub path: RustPath,
+    /// Names and values of variants, in the original order
+    pub variants: Vec<(String, i64)>,
+    /// Integer type corresponding to the underlying type of the C++ enum
+    pub underlying_type: RustType,
+}
+
+impl RustEnum {
+    /// Returns the name of the primitive type for the `#[repr(...)]` attribute.
+    fn repr_type(&self) -> Result<&'static str> {
+        let name = match &self.underlying_type {
+            RustType::Primitive(name) => name.clone(),
+            RustType::Common(RustCommonType {
+                path,
+                generic_arguments: None,
+            }) if path.is_child_of(&RustPath::from_good_str("std::os::raw")) => {
+                path.last().to_string()
+            }
+            _ => bail!("unsupported enum underlying type: {}", self.underlying_type),
+        };
+        let repr = match name.as_str() {
+            "i8" | "c_schar" => "i8",
+            "u8" | "c_uchar" => "u8",
+            "i16" | "c_short" => "i16",
+            "u16" | "c_ushort" => "u16",
+            "i32" | "c_int" => "i32",
+            "u32" | "c_uint" => "u32",
+            "i64" | "c_longlong" => "i64",
+            "u64" | "c_ulonglong" => "u64",
+            "isize" => "isize",
+            "usize" => "usize",
+            _ => bail!("unsupported enum underlying type: {}", self.underlying_type),
+        };
+        Ok(repr)
+    }
+
+    /// Generates Rust code of the enum with `#[repr(...)]` attribute
+    /// corresponding to the underlying type. Values of variants are preserved exactly.
+    /// Returns an error if two variants have the same value because Rust enums
+    /// don't allow that.
+    pub fn to_rust_code(&self) -> Result<String> {
+        let repr = self.repr_type()?;
+        let mut values = HashSet::new();
+        for (name, value) in &self.variants {
+            if !values.insert(*value) {
+                bail!(
+                    "duplicate value {} of variant {} in enum {}",
+                    value,
+                    name,
+                    self.path
+                );
+            }
+        }
+
+        let mut code = String::new();
+        code.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq)]\n");
+        code.push_str(&format!("#[repr({})]\n", repr));
+        code.push_str(&format!("pub enum {} {{\n", self.path.last()));
+        for (name, value) in &self.variants {
+            code.push_str(&format!("    {} = {},\n", name, value));
+        }
+        code.push_str("}\n");
+        Ok(code)
+    }
+}
+
 /// Information about a Qt slot wrapper on Rust side
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct RustQtSlotWrapper {
@@ -342,6 +417,18 @@ pub enum RustFunctionSelfArgKind {
     Value,
 }
 
+impl RustFunctionSelfArgKind {
+    /// Returns the receiver kind for a wrapper of a C++ member function:
+    /// `&self` for const methods and `&mut self` for non-const methods.
+    pub fn from_const_method(is_const_method: bool) -> Self {
+        if is_const_method {
+            RustFunctionSelfArgKind::ConstRef
+        } else {
+            RustFunctionSelfArgKind::MutRef
+        }
+    }
+}
+
 /// Information about an associated type value
 /// within a trait implementation.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -490,13 +577,1095 @@ impl RustFunctionCaptionStrategy {
     }
 }
 
-/// Information about an argument of a Rust FFI function.
+/// Argument of a function that may have a default value.
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
-pub struct RustFFIArgument {
-    /// Name of the argument.
-    pub name: String,
-    /// Type of the argument.
-    pub argument_type: RustType,
+pub struct RustDefaultedArgument {
+    /// Name and type of the argument.
+    pub argument: RustFunctionArgument,
+    /// Rust expression of the default value of the argument,
+    /// derived from the default value of the C++ argument.
+    /// `None` if the argument doesn't have a default value.
+    pub default_value: Option<String>,
+}
+
+/// One of the overloads generated for a function with default arguments.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RustDefaultArgumentsOverload {
+    /// Arguments accepted by the overload.
+    pub arguments: Vec<RustFunctionArgument>,
+    /// Default values that should be passed in place of the omitted
+    /// trailing arguments.
+    pub default_values: Vec<String>,
+}
+
+impl RustFunction {
+    /// Generates Rust code of the declaration of this FFI function
+    /// for an `extern "C"` block, e.g. `pub fn name(arg0: *const Foo) -> i32;`.
+    /// Returns an error if this is not a `RustFunctionKind::FfiFunction`.
+    pub fn ffi_declaration_to_code(&self, current_crate: Option<&str>) -> Result<String> {
+        if !self.kind.is_ffi_function() {
+            bail!("not an FFI function: {}", self.path);
+        }
+        Ok(format!(
+            "pub {};",
+            function_signature_to_code(
+                self.path.last(),
+                &self.arguments,
+                self.return_type.api_type(),
+                current_crate
+            )
+        ))
+    }
+
+    /// Returns a string that identifies the name and the types of this function
+    /// regardless of argument names and lifetimes, e.g. `foo(*const ::foo::Bar, i32) -> ()`.
+    /// Functions with equal keys are duplicates of each other.
+    pub fn signature_key(&self) -> String {
+        format!(
+            "{}({}) -> {}",
+            self.path.last(),
+            self.arguments
+                .iter()
+                .map(|arg| {
+                    arg.argument_type
+                        .api_type()
+                        .without_lifetimes()
+                        .to_rust_code(None)
+                })
+                .join(", "),
+            self.return_type
+                .api_type()
+                .without_lifetimes()
+                .to_rust_code(None)
+        )
+    }
+
+    /// Renames arguments with empty or duplicated names to `arg0`, `arg1`, etc.
+    /// (using the index of the argument) and adds "_" to names that are
+    /// reserved words in Rust. Unique names are preserved.
+    pub fn deduplicate_argument_names(&mut self) {
+        for arg in &mut self.arguments {
+            if !arg.name.is_empty() {
+                arg.name = sanitize_rust_identifier(&arg.name, false);
+            }
+        }
+        let mut counts = HashMap::new();
+        for arg in &self.arguments {
+            *counts.entry(arg.name.clone()).or_insert(0) += 1;
+        }
+        let is_valid = |name: &str| !name.is_empty() && counts[name] == 1;
+        let mut used_names: HashSet<String> = self
+            .arguments
+            .iter()
+            .filter(|arg| is_valid(&arg.name))
+            .map(|arg| arg.name.clone())
+            .collect();
+        let invalid_indexes = self
+            .arguments
+            .iter()
+            .enumerate()
+            .filter(|(_, arg)| !is_valid(&arg.name))
+            .map(|(index, _)| index)
+            .collect_vec();
+        for index in invalid_indexes {
+            let mut name = format!("arg{}", index);
+            while used_names.contains(&name) {
+                name.push('_');
+            }
+            used_names.insert(name.clone());
+            self.arguments[index].name = name;
+        }
+    }
+}
+
+/// Generates Rust code of the signature of a function `name` with `arguments`
+/// and `return_type`, e.g. `fn name<'a>(arg: &'a T) -> &'a U`.
+/// API types of `arguments` are used.
+/// Explicit lifetimes are omitted if allowed by lifetime elision rules.
+pub fn function_signature_to_code(
+    name: &str,
+    arguments: &[RustFunctionArgument],
+    return_type: &RustType,
+    current_crate: Option<&str>,
+) -> String {
+    function_signature_with_bounds_to_code(
+        name,
+        arguments,
+        return_type,
+        &BTreeMap::new(),
+        current_crate,
+    )
+}
+
+/// Adds names of generic parameters used in `rust_type` to `output`.
+fn collect_generic_parameters(rust_type: &RustType, output: &mut Vec<String>) {
+    match rust_type {
+        RustType::GenericParameter(name) => {
+            if !output.contains(name) {
+                output.push(name.clone());
+            }
+        }
+        RustType::Tuple(types) => {
+            for t in types {
+                collect_generic_parameters(t, output);
+            }
+        }
+        RustType::Common(common)
+        | RustType::ImplTrait(common)
+        | RustType::TraitObject {
+            trait_type: common, ..
+        } => {
+            for t in common.generic_arguments.iter().flatten() {
+                collect_generic_parameters(t, output);
+            }
+        }
+        RustType::FunctionPointer(function) => {
+            for t in &function.arguments {
+                collect_generic_parameters(t, output);
+            }
+            collect_generic_parameters(&function.return_type, output);
+        }
+        RustType::PointerLike { target, .. } => collect_generic_parameters(target, output),
+        RustType::Slice(item) | RustType::Array { item, .. } => {
+            collect_generic_parameters(item, output)
+        }
+        RustType::Primitive(_) => {}
+    }
+}
+
+/// Same as `function_signature_to_code`, but also declares generic type parameters
+/// used in the signature and emits a `where` clause with trait `bounds`
+/// of the generic parameters (e.g. `where T: Into<QString> + Clone`).
+/// `bounds` is indexed by the name of the generic parameter.
+/// No `where` clause is emitted if there are no bounds.
+pub fn function_signature_with_bounds_to_code(
+    name: &str,
+    arguments: &[RustFunctionArgument],
+    return_type: &RustType,
+    bounds: &BTreeMap<String, Vec<RustType>>,
+    current_crate: Option<&str>,
+) -> String {
+    signature_with_receiver_to_code(
+        name,
+        RustFunctionSelfArgKind::None,
+        None,
+        arguments,
+        return_type,
+        bounds,
+        current_crate,
+    )
+}
+
+/// Implementation of `function_signature_with_bounds_to_code` that also
+/// emits a `self_arg_kind` receiver before `arguments`. If the receiver is
+/// a reference, it's annotated with `self_lifetime` unless lifetimes are elided.
+fn signature_with_receiver_to_code(
+    name: &str,
+    self_arg_kind: RustFunctionSelfArgKind,
+    self_lifetime: Option<&str>,
+    arguments: &[RustFunctionArgument],
+    return_type: &RustType,
+    bounds: &BTreeMap<String, Vec<RustType>>,
+    current_crate: Option<&str>,
+) -> String {
+    let argument_types = arguments
+        .iter()
+        .map(|arg| arg.argument_type.api_type().clone())
+        .collect_vec();
+    let is_ref_receiver = match self_arg_kind {
+        RustFunctionSelfArgKind::ConstRef | RustFunctionSelfArgKind::MutRef => true,
+        RustFunctionSelfArgKind::None | RustFunctionSelfArgKind::Value => false,
+    };
+    let elide = if is_ref_receiver {
+        can_elide_method_lifetimes(self_lifetime, &argument_types, return_type)
+    } else {
+        can_elide_lifetimes(&argument_types, return_type)
+    };
+
+    let type_to_code = |t: &RustType| {
+        if elide {
+            t.without_lifetimes().to_rust_code(current_crate)
+        } else {
+            t.to_rust_code(current_crate)
+        }
+    };
+
+    let self_lifetime = if elide || !is_ref_receiver {
+        None
+    } else {
+        self_lifetime
+    };
+    let lifetimes = if elide {
+        Vec::new()
+    } else {
+        argument_types
+            .iter()
+            .chain(iter::once(return_type))
+            .flat_map(RustType::reference_lifetimes)
+            .flatten()
+            .chain(self_lifetime)
+            .filter(|l| *l != "static")
+            .unique()
+            .map(|l| format!("'{}", l))
+            .collect_vec()
+    };
+    let mut type_parameters = Vec::new();
+    for t in argument_types.iter().chain(iter::once(return_type)) {
+        collect_generic_parameters(t, &mut type_parameters);
+    }
+    for parameter in bounds.keys() {
+        if !type_parameters.contains(parameter) {
+            type_parameters.push(parameter.clone());
+        }
+    }
+    let generic_args = lifetimes.into_iter().chain(type_parameters).collect_vec();
+    let generic_args_text = if generic_args.is_empty() {
+        String::new()
+    } else {
+        format!("<{}>", generic_args.join(", "))
+    };
+
+    let where_items = bounds
+        .iter()
+        .filter(|(_, parameter_bounds)| !parameter_bounds.is_empty())
+        .map(|(parameter, parameter_bounds)| {
+            format!(
+                "{}: {}",
+                parameter,
+                parameter_bounds
+                    .iter()
+                    .map(|bound| bound.to_rust_code(current_crate))
+                    .join(" + ")
+            )
+        })
+        .collect_vec();
+    let where_text = if where_items.is_empty() {
+        String::new()
+    } else {
+        format!(" where {}", where_items.join(", "))
+    };
+
+    let return_type_text = if return_type.is_unit() {
+        String::new()
+    } else {
+        format!(" -> {}", type_to_code(return_type))
+    };
+
+    let self_lifetime_text = self_lifetime.map_or(String::new(), |l| format!("'{} ", l));
+    let receiver = match self_arg_kind {
+        RustFunctionSelfArgKind::None => None,
+        RustFunctionSelfArgKind::ConstRef => Some(format!("&{}self", self_lifetime_text)),
+        RustFunctionSelfArgKind::MutRef => Some(format!("&{}mut self", self_lifetime_text)),
+        RustFunctionSelfArgKind::Value => Some("self".to_string()),
+    };
+    format!(
+        "fn {}{}({}){}{}",
+        name,
+        generic_args_text,
+        receiver
+            .into_iter()
+            .chain(arguments.iter().map(|arg| format!(
+                "{}: {}",
+                arg.name,
+                type_to_code(arg.argument_type.api_type())
+            )))
+            .join(", "),
+        return_type_text,
+        where_text
+    )
+}
+
+/// Generates Rust code of the signature of a public API function `name`
+/// placed in `scope`, including the indentation and the visibility
+/// (trait implementation methods are not marked `pub`).
+///
+/// If `self_arg_kind` is not `None`, the first of `arguments` must be
+/// a pointer to the object (as in the corresponding FFI function),
+/// and it's replaced by the `self`, `&self` or `&mut self` receiver.
+/// Free functions can't have a receiver.
+///
+/// Lifetimes of the return type that are not bound by other arguments
+/// are considered the lifetime of a reference receiver, so that elision
+/// follows the `&self` rule. If lifetimes can't be elided, the receiver
+/// is rendered as `&'a self`. An error is returned if the return type has
+/// multiple such lifetimes.
+pub fn scoped_function_signature_to_code(
+    name: &str,
+    arguments: &[RustFunctionArgument],
+    return_type: &RustType,
+    scope: &RustFunctionScope,
+    self_arg_kind: RustFunctionSelfArgKind,
+    current_crate: Option<&str>,
+) -> Result<String> {
+    let mut signature = if self_arg_kind != RustFunctionSelfArgKind::None {
+        if let RustFunctionScope::Free = scope {
+            bail!("free function can't have a self argument: {}", name);
+        }
+        let self_arg_type = match arguments.first() {
+            Some(arg) => arg.argument_type.ffi_type(),
+            None => bail!("missing self argument: {}", name),
+        };
+        let is_const = match self_arg_type {
+            RustType::PointerLike {
+                kind: RustPointerLikeTypeKind::Pointer,
+                is_const,
+                ..
+            } => *is_const,
+            _ => bail!("self argument must be a pointer: {}", name),
+        };
+        let expected_const = match self_arg_kind {
+            RustFunctionSelfArgKind::ConstRef => Some(true),
+            RustFunctionSelfArgKind::MutRef => Some(false),
+            _ => None,
+        };
+        if expected_const.map_or(false, |expected| expected != is_const) {
+            bail!(
+                "self argument type {} doesn't match {:?}: {}",
+                self_arg_type,
+                self_arg_kind,
+                name
+            );
+        }
+
+        // Lifetimes of the return type that are not bound by other arguments
+        // can only be the lifetime of the receiver. Otherwise, they would be
+        // chosen by the caller, which is unsound.
+        let arguments = &arguments[1..];
+        let bound_lifetimes = arguments
+            .iter()
+            .flat_map(|arg| arg.argument_type.api_type().reference_lifetimes())
+            .flatten()
+            .collect::<HashSet<_>>();
+        let unbound_lifetimes = return_type
+            .reference_lifetimes()
+            .into_iter()
+            .flatten()
+            .filter(|l| *l != "static" && !bound_lifetimes.contains(l))
+            .unique()
+            .collect_vec();
+        let self_lifetime = match self_arg_kind {
+            RustFunctionSelfArgKind::ConstRef | RustFunctionSelfArgKind::MutRef => {
+                if unbound_lifetimes.len() > 1 {
+                    bail!(
+                        "return type {} has multiple lifetimes not bound by arguments: {}",
+                        return_type,
+                        name
+                    );
+                }
+                unbound_lifetimes.first().cloned()
+            }
+            RustFunctionSelfArgKind::None | RustFunctionSelfArgKind::Value => None,
+        };
+        signature_with_receiver_to_code(
+            name,
+            self_arg_kind,
+            self_lifetime,
+            arguments,
+            return_type,
+            &BTreeMap::new(),
+            current_crate,
+        )
+    } else {
+        function_signature_to_code(name, arguments, return_type, current_crate)
+    };
+
+    let prefix = match scope {
+        RustFunctionScope::Free => "pub ",
+        RustFunctionScope::Impl { .. } => "    pub ",
+        RustFunctionScope::TraitImpl => "    ",
+    };
+    signature.insert_str(0, prefix);
+    Ok(signature)
+}
+
+/// Generates Rust code of the signature of a method `name` wrapping a C++ member
+/// function. The receiver is `&self` for a const C++ method (`is_const_method`)
+/// and `&mut self` otherwise. The first of `arguments` must be the `this` pointer
+/// of the FFI function, and its constness must match `is_const_method`,
+/// so a const method can't produce a `&mut self` receiver.
+/// See also `scoped_function_signature_to_code`.
+pub fn method_signature_to_code(
+    name: &str,
+    arguments: &[RustFunctionArgument],
+    return_type: &RustType,
+    scope: &RustFunctionScope,
+    is_const_method: bool,
+    current_crate: Option<&str>,
+) -> Result<String> {
+    scoped_function_signature_to_code(
+        name,
+        arguments,
+        return_type,
+        scope,
+        RustFunctionSelfArgKind::from_const_method(is_const_method),
+        current_crate,
+    )
+}
+
+/// Generates an `extern "C"` block declaring `functions`. If `link_name` is specified,
+/// the block is annotated with `#[link(name = ...)]`.
+/// Functions are sorted by name to keep the output stable.
+pub fn extern_block_to_code(
+    functions: &[RustFunction],
+    link_name: Option<&str>,
+    current_crate: Option<&str>,
+) -> Result<String> {
+    let mut code = String::new();
+    if let Some(link_name) = link_name {
+        code.push_str(&format!("#[link(name = \"{}\")]\n", link_name));
+    }
+    code.push_str("extern \"C\" {\n");
+    for function in functions
+        .iter()
+        .sorted_by(|a, b| a.path.last().cmp(b.path.last()))
+    {
+        code.push_str(&format!(
+            "    {}\n",
+            function.ffi_declaration_to_code(current_crate)?
+        ));
+    }
+    code.push_str("}\n");
+    Ok(code)
+}
+
+/// Kind of a native library linked to the crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RustLinkKind {
+    Dylib,
+    Static,
+}
+
+impl RustLinkKind {
+    /// Returns the value of `kind` in the `#[link]` attribute.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RustLinkKind::Dylib => "dylib",
+            RustLinkKind::Static => "static",
+        }
+    }
+}
+
+/// Generates a `#[link(name = ..., kind = ...)]` attribute for the library `library_name`.
+/// `library_name` can also be a file name of the library (e.g. `libfoo.so` or `foo.dll`),
+/// in which case the platform-specific prefix and suffix are removed. On Windows,
+/// a DLL is linked through its import library, which has the same stem,
+/// so `foo.dll` is linked as `foo`.
+pub fn link_attribute_to_code(library_name: &str, kind: RustLinkKind) -> String {
+    let suffix = match kind {
+        RustLinkKind::Dylib => dylib_suffix(),
+        RustLinkKind::Static => staticlib_suffix(),
+    };
+    let mut name = library_name;
+    if name.ends_with(suffix) {
+        name = &name[..name.len() - suffix.len()];
+        let prefix = dylib_prefix();
+        if !prefix.is_empty() && name.starts_with(prefix) {
+            name = &name[prefix.len()..];
+        }
+    }
+    format!("#[link(name = \"{}\", kind = \"{}\")]", name, kind.as_str())
+}
+
+/// Generates an `extern "C"` block declaring `functions`
+/// annotated with the `#[link]` attribute for `library_name` of `kind`.
+pub fn linked_extern_block_to_code(
+    functions: &[RustFunction],
+    library_name: &str,
+    kind: RustLinkKind,
+    current_crate: Option<&str>,
+) -> Result<String> {
+    Ok(format!(
+        "{}\n{}",
+        link_attribute_to_code(library_name, kind),
+        extern_block_to_code(functions, None, current_crate)?
+    ))
+}
+
+/// A tree of modules containing FFI function declarations.
+///
+/// Unlike `RustModule`, which is stored in the database and refers to its items by path,
+/// this type owns its contents and can be converted to code directly.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RustFfiModule {
+    /// Path to the module. The last part is used as the module name.
+    pub path: RustPath,
+    /// FFI functions declared directly in this module.
+    pub ffi_functions: Vec<RustFunction>,
+    /// Nested modules.
+    pub submodules: Vec<RustFfiModule>,
+}
+
+impl RustFfiModule {
+    /// Generates a `pub mod` block with nested modules.
+    /// Functions and submodules are sorted by name to keep the output stable.
+    pub fn to_rust_code(&self, current_crate: Option<&str>) -> Result<String> {
+        let mut code = format!("pub mod {} {{\n", self.path.last());
+        let mut content = String::new();
+        if !self.ffi_functions.is_empty() {
+            content.push_str(&extern_block_to_code(
+                &self.ffi_functions,
+                None,
+                current_crate,
+            )?);
+        }
+        for submodule in self
+            .submodules
+            .iter()
+            .sorted_by(|a, b| a.path.last().cmp(b.path.last()))
+        {
+            if !content.is_empty() {
+                content.push('\n');
+            }
+            content.push_str(&submodule.to_rust_code(current_crate)?);
+        }
+        for line in content.lines() {
+            if line.is_empty() {
+                code.push('\n');
+            } else {
+                code.push_str(&format!("    {}\n", line));
+            }
+        }
+        code.push_str("}\n");
+        Ok(code)
+    }
+}
+
+/// Generates a wrapper type `path` declared as a transparent newtype over
+/// the raw pointer type `pointer_type` (e.g. `*mut ::qt_core_sys::QString`).
+/// The wrapper has private `from_ptr` and `as_ptr` accessors for use
+/// by the generated API functions.
+pub fn pointer_wrapper_to_code(
+    path: &RustPath,
+    pointer_type: &RustType,
+    current_crate: Option<&str>,
+) -> Result<String> {
+    if !pointer_type.is_ptr() {
+        bail!("wrapped type must be a raw pointer: {}", pointer_type);
+    }
+    let name = path.last();
+    let pointer_code = pointer_type.to_rust_code(current_crate);
+    let mut code = String::new();
+    code.push_str("#[repr(transparent)]\n");
+    code.push_str(&format!("pub struct {}({});\n\n", name, pointer_code));
+    code.push_str(&format!("impl {} {{\n", name));
+    code.push_str("    #[allow(dead_code)]\n");
+    code.push_str(&format!(
+        "    fn from_ptr(ptr: {}) -> Self {{\n        {}(ptr)\n    }}\n\n",
+        pointer_code, name
+    ));
+    code.push_str("    #[allow(dead_code)]\n");
+    code.push_str(&format!(
+        "    fn as_ptr(&self) -> {} {{\n        self.0\n    }}\n",
+        pointer_code
+    ));
+    code.push_str("}\n");
+    Ok(code)
+}
+
+/// Generates an `impl Drop` block for the wrapper type `type_path` that calls
+/// the FFI function `destructor` on the wrapped pointer returned by `as_ptr()`
+/// (see `pointer_wrapper_to_code`).
+/// The destructor must have a single pointer argument and no return value.
+/// `destructor` is referenced by its full path.
+pub fn drop_impl_to_code(
+    type_path: &RustPath,
+    destructor: &RustFunction,
+    current_crate: Option<&str>,
+) -> Result<String> {
+    if destructor.arguments.len() != 1 || !destructor.arguments[0].argument_type.ffi_type().is_ptr()
+    {
+        bail!(
+            "destructor must have a single pointer argument: {:?}",
+            destructor
+        );
+    }
+    if !destructor.return_type.ffi_type().is_unit() {
+        bail!("destructor must not return a value: {:?}", destructor);
+    }
+    let type_code = RustType::Common(RustCommonType {
+        path: type_path.clone(),
+        generic_arguments: None,
+    })
+    .to_rust_code(current_crate);
+    let mut code = format!("impl Drop for {} {{\n", type_code);
+    code.push_str("    fn drop(&mut self) {\n");
+    code.push_str(&format!(
+        "        unsafe {{ {}(self.as_ptr()) }}\n",
+        destructor.path.to_rust_code(current_crate)
+    ));
+    code.push_str("    }\n");
+    code.push_str("}\n");
+    Ok(code)
+}
+
+/// Generates conversion of the wrapper type `source_path` to `target_type`
+/// that calls the FFI function `ffi_function` wrapping a C++ conversion operator
+/// (`CppOperator::Conversion`). The FFI function must have a single const pointer
+/// argument and return `target_type` directly.
+///
+/// An implicit conversion is represented by an `impl From<&Source> for Target` block.
+/// If `is_explicit` is true, an inherent method `to_<target caption>` is generated
+/// instead, following the naming of other conversion methods.
+/// The wrapped pointer is obtained with `as_ptr()` (see `pointer_wrapper_to_code`).
+/// `ffi_function` is referenced by its full path.
+pub fn conversion_to_code(
+    source_path: &RustPath,
+    target_type: &RustType,
+    ffi_function: &RustFunction,
+    is_explicit: bool,
+    current_crate: Option<&str>,
+) -> Result<String> {
+    let is_const_ptr_arg = ffi_function.arguments.len() == 1
+        && match ffi_function.arguments[0].argument_type.ffi_type() {
+            RustType::PointerLike {
+                kind: RustPointerLikeTypeKind::Pointer,
+                is_const,
+                ..
+            } => *is_const,
+            _ => false,
+        };
+    if !is_const_ptr_arg {
+        bail!(
+            "conversion function must have a single const pointer argument: {:?}",
+            ffi_function
+        );
+    }
+    if ffi_function.return_type.ffi_type() != target_type {
+        bail!(
+            "conversion function must return {:?}: {:?}",
+            target_type,
+            ffi_function
+        );
+    }
+    let source_code = RustType::Common(RustCommonType {
+        path: source_path.clone(),
+        generic_arguments: None,
+    })
+    .to_rust_code(current_crate);
+    let target_code = target_type.to_rust_code(current_crate);
+    let function_code = ffi_function.path.to_rust_code(current_crate);
+
+    let mut code = String::new();
+    if is_explicit {
+        let method_name = format!(
+            "to_{}",
+            target_type.caption(source_path, RustTypeCaptionStrategy::LastName)?
+        );
+        code.push_str(&format!("impl {} {{\n", source_code));
+        code.push_str(&format!(
+            "    pub fn {}(&self) -> {} {{\n",
+            sanitize_rust_identifier(&method_name, false),
+            target_code
+        ));
+        code.push_str(&format!(
+            "        unsafe {{ {}(self.as_ptr()) }}\n",
+            function_code
+        ));
+    } else {
+        code.push_str(&format!(
+            "impl<'a> From<&'a {}> for {} {{\n",
+            source_code, target_code
+        ));
+        code.push_str(&format!(
+            "    fn from(value: &'a {}) -> Self {{\n",
+            source_code
+        ));
+        code.push_str(&format!(
+            "        unsafe {{ {}(value.as_ptr()) }}\n",
+            function_code
+        ));
+    }
+    code.push_str("    }\n");
+    code.push_str("}\n");
+    Ok(code)
+}
+
+/// Returns true if `function` has two const pointer arguments
+/// (`this` and the other object), as FFI functions wrapping C++ comparison operators.
+fn is_binary_const_function(function: &RustFunction) -> bool {
+    function.arguments.len() == 2
+        && function
+            .arguments
+            .iter()
+            .all(|arg| match arg.argument_type.ffi_type() {
+                RustType::PointerLike {
+                    kind: RustPointerLikeTypeKind::Pointer,
+                    is_const,
+                    ..
+                } => *is_const,
+                _ => false,
+            })
+}
+
+/// Generates an `impl PartialEq` block for the wrapper type `type_path` that calls
+/// the FFI function `eq_function` wrapping C++ `operator==`. The FFI function must
+/// have two const pointer arguments (`this` and the other object) and return `bool`.
+///
+/// The FFI function wrapping `operator!=` (`ne_function`) is not called because
+/// `PartialEq::ne` is derived from `eq`. If it's provided, a comment noting this
+/// is added to the generated code.
+/// The FFI functions are referenced by their full paths.
+pub fn partial_eq_impl_to_code(
+    type_path: &RustPath,
+    eq_function: &RustFunction,
+    ne_function: Option<&RustFunction>,
+    current_crate: Option<&str>,
+) -> Result<String> {
+    if !is_binary_const_function(eq_function) {
+        bail!(
+            "equality function must have two const pointer arguments: {:?}",
+            eq_function
+        );
+    }
+    if eq_function.return_type.ffi_type() != &RustType::Primitive("bool".into()) {
+        bail!("equality function must return bool: {:?}", eq_function);
+    }
+    let type_code = RustType::Common(RustCommonType {
+        path: type_path.clone(),
+        generic_arguments: None,
+    })
+    .to_rust_code(current_crate);
+    let mut code = format!("impl PartialEq for {} {{\n", type_code);
+    if let Some(ne_function) = ne_function {
+        code.push_str(&format!(
+            "    // `{}` (`operator!=`) is not used because `ne` is derived from `eq`\n",
+            ne_function.path.last()
+        ));
+    }
+    code.push_str("    fn eq(&self, other: &Self) -> bool {\n");
+    code.push_str(&format!(
+        "        unsafe {{ {}(self.as_ptr(), other.as_ptr()) }}\n",
+        eq_function.path.to_rust_code(current_crate)
+    ));
+    code.push_str("    }\n");
+    code.push_str("}\n");
+    Ok(code)
+}
+
+/// Generates an `impl PartialOrd` block (and an `impl Ord` block if `is_total` is true)
+/// for the wrapper type `type_path` using FFI functions wrapping C++ comparison operators.
+///
+/// If `three_way_function` (`operator<=>`) is provided, its integer result is
+/// compared with zero to produce the ordering. Otherwise, the ordering is synthesized
+/// from `less_than_function` (`operator<`) and `eq_function` (`operator==`),
+/// which must be provided in this case. All functions must have two const pointer
+/// arguments. `Ord` additionally requires `Eq` to be implemented for the type.
+/// The FFI functions are referenced by their full paths.
+pub fn partial_ord_impl_to_code(
+    type_path: &RustPath,
+    three_way_function: Option<&RustFunction>,
+    less_than_function: Option<&RustFunction>,
+    eq_function: Option<&RustFunction>,
+    is_total: bool,
+    current_crate: Option<&str>,
+) -> Result<String> {
+    let bool_type = RustType::Primitive("bool".into());
+    let call = |function: &RustFunction, a: &str, b: &str| {
+        format!(
+            "{}({}.as_ptr(), {}.as_ptr())",
+            function.path.to_rust_code(current_crate),
+            a,
+            b
+        )
+    };
+    let ordering = "::std::cmp::Ordering";
+    let mut body = Vec::new();
+    if let Some(function) = three_way_function {
+        let is_int = match function.return_type.ffi_type() {
+            RustType::Primitive(name) => name == "i32",
+            RustType::Common(RustCommonType { path, .. }) => {
+                path == &RustPath::from_good_str("std::os::raw::c_int")
+            }
+            _ => false,
+        };
+        if !is_binary_const_function(function) || !is_int {
+            bail!(
+                "three-way comparison function must have two const pointer arguments \
+                 and return i32: {:?}",
+                function
+            );
+        }
+        body.push(format!(
+            "let result = unsafe {{ {} }};",
+            call(function, "self", "other")
+        ));
+        body.push("result.cmp(&0)".to_string());
+    } else {
+        let (less_than_function, eq_function) = match (less_than_function, eq_function) {
+            (Some(less_than), Some(eq)) => (less_than, eq),
+            _ => bail!("either three-way comparison or both `<` and `==` functions are required"),
+        };
+        for function in &[less_than_function, eq_function] {
+            if !is_binary_const_function(function) || function.return_type.ffi_type() != &bool_type
+            {
+                bail!(
+                    "comparison function must have two const pointer arguments \
+                     and return bool: {:?}",
+                    function
+                );
+            }
+        }
+        body.push("unsafe {".to_string());
+        body.push(format!(
+            "    if {} {{",
+            call(less_than_function, "self", "other")
+        ));
+        body.push(format!("        {}::Less", ordering));
+        body.push(format!(
+            "    }} else if {} {{",
+            call(eq_function, "self", "other")
+        ));
+        body.push(format!("        {}::Equal", ordering));
+        if !is_total {
+            // neither `a < b`, `a == b` nor `b < a` holds for unordered values
+            body.push(format!(
+                "    }} else if !{} {{",
+                call(less_than_function, "other", "self")
+            ));
+            body.push("        return None;".to_string());
+        }
+        body.push("    } else {".to_string());
+        body.push(format!("        {}::Greater", ordering));
+        body.push("    }".to_string());
+        body.push("}".to_string());
+    }
+
+    let type_code = RustType::Common(RustCommonType {
+        path: type_path.clone(),
+        generic_arguments: None,
+    })
+    .to_rust_code(current_crate);
+    let mut code = format!("impl PartialOrd for {} {{\n", type_code);
+    code.push_str(&format!(
+        "    fn partial_cmp(&self, other: &Self) -> Option<{}> {{\n",
+        ordering
+    ));
+    let body_code = |indent: &str| {
+        body.iter()
+            .map(|line| format!("{}{}\n", indent, line))
+            .join("")
+    };
+    if is_total {
+        code.push_str("        Some(self.cmp(other))\n");
+    } else {
+        code.push_str("        let ordering = {\n");
+        code.push_str(&body_code("            "));
+        code.push_str("        };\n");
+        code.push_str("        Some(ordering)\n");
+    }
+    code.push_str("    }\n");
+    code.push_str("}\n");
+    if is_total {
+        code.push_str(&format!("\nimpl Ord for {} {{\n", type_code));
+        code.push_str(&format!(
+            "    fn cmp(&self, other: &Self) -> {} {{\n",
+            ordering
+        ));
+        code.push_str(&body_code("        "));
+        code.push_str("    }\n");
+        code.push_str("}\n");
+    }
+    Ok(code)
+}
+
+/// Generates a bitflags-style wrapper type `path` for a Qt flags enum
+/// with flag `values`. The type is a transparent newtype over `c_uint`
+/// (the FFI type of `QFlags`, see `RustToFfiTypeConversion::QFlagsToUInt`)
+/// and implements the bitwise operator traits corresponding to
+/// C++ operators `|`, `&`, `^` and `~`.
+pub fn flags_wrapper_to_code(path: &RustPath, values: &[(String, i64)]) -> Result<String> {
+    let name = path.last();
+    let int_type = "::std::os::raw::c_uint";
+    let mut code = String::new();
+    code.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]\n");
+    code.push_str("#[repr(transparent)]\n");
+    code.push_str(&format!("pub struct {}({});\n\n", name, int_type));
+
+    code.push_str(&format!("impl {} {{\n", name));
+    for (value_name, value) in values {
+        if *value < 0 || *value > i64::from(u32::max_value()) {
+            bail!("invalid flag value for {}::{}: {}", path, value_name, value);
+        }
+        code.push_str("    #[allow(non_upper_case_globals)]\n");
+        code.push_str(&format!(
+            "    pub const {}: {} = {}({});\n",
+            value_name, name, name, value
+        ));
+    }
+    code.push_str(&format!(
+        "\n    pub fn from_int(value: {}) -> Self {{\n        {}(value)\n    }}\n",
+        int_type, name
+    ));
+    code.push_str(&format!(
+        "\n    pub fn to_int(&self) -> {} {{\n        self.0\n    }}\n",
+        int_type
+    ));
+    code.push_str("}\n\n");
+
+    code.push_str(&format!(
+        "impl From<{int}> for {name} {{\n    fn from(value: {int}) -> Self {{\n        \
+         {name}(value)\n    }}\n}}\n\n",
+        int = int_type,
+        name = name
+    ));
+    code.push_str(&format!(
+        "impl From<{name}> for {int} {{\n    fn from(value: {name}) -> Self {{\n        \
+         value.0\n    }}\n}}\n",
+        int = int_type,
+        name = name
+    ));
+
+    let binary_operators = [
+        CppOperator::BitwiseOr,
+        CppOperator::BitwiseAnd,
+        CppOperator::BitwiseXor,
+    ];
+    for operator in &binary_operators {
+        let trait_path = operator
+            .rust_trait()
+            .expect("bitwise operator must have a trait");
+        let method = operator
+            .rust_trait_method()
+            .expect("bitwise operator must have a trait method");
+        let symbol = operator
+            .info()
+            .function_name_suffix
+            .expect("bitwise operator must have a symbol");
+        code.push_str(&format!(
+            "\nimpl ::{trait_path} for {name} {{\n    type Output = {name};\n\n    \
+             fn {method}(self, other: {name}) -> {name} {{\n        \
+             {name}(self.0 {symbol} other.0)\n    }}\n}}\n",
+            trait_path = trait_path,
+            name = name,
+            method = method,
+            symbol = symbol
+        ));
+    }
+
+    let operator = CppOperator::BitwiseNot;
+    code.push_str(&format!(
+        "\nimpl ::{trait_path} for {name} {{\n    type Output = {name};\n\n    \
+         fn {method}(self) -> {name} {{\n        {name}(!self.0)\n    }}\n}}\n",
+        trait_path = operator
+            .rust_trait()
+            .expect("bitwise operator must have a trait"),
+        name = name,
+        method = operator
+            .rust_trait_method()
+            .expect("bitwise operator must have a trait method"),
+    ));
+    Ok(code)
+}
+
+/// Generates unique names for a set of possibly overloaded functions.
+/// `functions` contains the natural path and the argument types of each function.
+/// Functions with a unique natural path keep it. For each group of functions
+/// sharing the same path, captions of the argument types are appended to
+/// the last part of the path (e.g. `set`, `set_i32`, `set_q_string`).
+/// If that's still not enough, or if a generated path clashes with the natural path
+/// of any function in `functions`, the index within the group is appended as well.
+/// Returned paths correspond to the items of `functions`.
+pub fn disambiguate_overloads(functions: &[(RustPath, Vec<RustType>)]) -> Result<Vec<RustPath>> {
+    let mut counts = HashMap::new();
+    for (path, _) in functions {
+        *counts.entry(path).or_insert(0) += 1;
+    }
+
+    let mut result = functions
+        .iter()
+        .map_if_ok(|(path, arguments)| -> Result<RustPath> {
+            if counts[path] == 1 || arguments.is_empty() {
+                return Ok(path.clone());
+            }
+            let suffix = arguments
+                .iter()
+                .map_if_ok(|t| t.caption_or_void(path, RustTypeCaptionStrategy::LastName))?
+                .join("_");
+            let name = sanitize_rust_identifier(&format!("{}_{}", path.last(), suffix), false);
+            Ok(path.parent()?.join(name))
+        })?;
+
+    // Natural paths are only indexed if they are still ambiguous. Generated paths
+    // are also indexed if they clash with a natural path of another function.
+    let mut new_counts = HashMap::new();
+    for (path, (natural_path, _)) in result.iter().zip(functions) {
+        *new_counts
+            .entry((path.clone(), path == natural_path))
+            .or_insert(0) += 1;
+    }
+    let needs_index = result
+        .iter()
+        .zip(functions)
+        .map(|(path, (natural_path, _))| {
+            let is_natural = path == natural_path;
+            let is_clashing = !is_natural && counts.contains_key(path);
+            new_counts[&(path.clone(), is_natural)] > 1 || is_clashing
+        })
+        .collect_vec();
+    let mut used_paths: HashSet<RustPath> = functions
+        .iter()
+        .map(|(path, _)| path.clone())
+        .chain(
+            result
+                .iter()
+                .zip(&needs_index)
+                .filter(|(_, needs_index)| !**needs_index)
+                .map(|(path, _)| path.clone()),
+        )
+        .collect();
+    let mut indexes = HashMap::new();
+    for (path, needs_index) in result.iter_mut().zip(needs_index) {
+        if needs_index {
+            let index = indexes.entry(path.clone()).or_insert(0);
+            let mut new_path = path.clone();
+            loop {
+                *new_path.last_mut() = format!("{}_{}", path.last(), index);
+                *index += 1;
+                if used_paths.insert(new_path.clone()) {
+                    break;
+                }
+            }
+            *path = new_path;
+        }
+    }
+    Ok(result)
+}
+
+/// Generates overloads of a function with `arguments` by progressively
+/// dropping trailing arguments that have default values, starting with
+/// the overload that accepts all arguments. Arguments with default values
+/// must not be followed by arguments without default values, as in C++.
+pub fn default_arguments_overloads(
+    arguments: &[RustDefaultedArgument],
+) -> Result<Vec<RustDefaultArgumentsOverload>> {
+    let required_count = arguments
+        .iter()
+        .take_while(|arg| arg.default_value.is_none())
+        .count();
+    if let Some(arg) = arguments[required_count..]
+        .iter()
+        .find(|arg| arg.default_value.is_none())
+    {
+        bail!(
+            "argument without default value follows default arguments: {}",
+            arg.argument.name
+        );
+    }
+
+    let result = (required_count..=arguments.len())
+        .rev()
+        .map(|count| RustDefaultArgumentsOverload {
+            arguments: arguments[..count]
+                .iter()
+                .map(|arg| arg.argument.clone())
+                .collect(),
+            default_values: arguments[count..]
+                .iter()
+                .filter_map(|arg| arg.default_value.clone())
+                .collect(),
+        })
+        .collect();
+    Ok(result)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -789,8 +1958,8 @@ impl RustItem {
             RustItem::EnumValue(data) => format!("enum value {}", data.path.full_name(None)),
             RustItem::TraitImpl(data) => format!(
                 "impl {} for {}",
-                rust_common_type_to_code(&data.trait_type, None),
-                rust_type_to_code(&data.target_type, None)
+                data.trait_type.to_rust_code(None),
+                data.target_type.to_rust_code(None)
             ),
             RustItem::ExtraImpl(data) => format!("extra impl {:?}", data.kind),
             RustItem::Function(data) => format!("fn {}", data.path.full_name(None)),
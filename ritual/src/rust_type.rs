@@ -1,11 +1,47 @@
+use crate::cpp_type::CppType;
 use crate::rust_info::RustTypeCaptionStrategy;
 use itertools::Itertools;
 use ritual_common::errors::{bail, Error, Result};
 use ritual_common::string_utils::CaseOperations;
-use ritual_common::utils::MapIfOk;
+use ritual_common::utils::{hash_bytes, MapIfOk};
 use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::iter;
+use std::mem;
+use std::os::raw;
 use std::str::FromStr;
 
+/// Rust keywords (including reserved ones) that can't be used as identifiers.
+pub const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "alignof", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl",
+    "in", "let", "loop", "macro", "match", "mod", "move", "mut", "offsetof", "override", "priv",
+    "proc", "pub", "pure", "ref", "return", "self", "Self", "sizeof", "static", "struct", "super",
+    "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual", "where",
+    "while", "yield",
+];
+
+/// Adds "_" to a string if it is a reserved word in Rust
+pub fn sanitize_rust_identifier(name: &str, is_module: bool) -> String {
+    match name {
+        "lib" | "main" if is_module => format!("{}_", name),
+        _ if RUST_KEYWORDS.contains(&name) => format!("{}_", name),
+        _ => name.to_string(),
+    }
+}
+
+/// Escapes a Rust keyword with the `r#` raw identifier prefix.
+/// Keywords that can't be used as raw identifiers (`self`, `super`, `crate`, `Self`)
+/// get the "_" suffix instead.
+pub fn escape_rust_identifier(name: &str) -> String {
+    match name {
+        "self" | "super" | "crate" | "Self" => format!("{}_", name),
+        _ if RUST_KEYWORDS.contains(&name) => format!("r#{}", name),
+        _ => name.to_string(),
+    }
+}
+
 /// Rust identifier. Represented by
 /// a vector of name parts. First part is name of the crate,
 /// last part is own name of the entity,
@@ -20,14 +56,16 @@ impl FromStr for RustPath {
     type Err = Error;
 
     fn from_str(str: &str) -> Result<Self> {
-        let parts = str.split("::").map(String::from).collect_vec();
-        if parts.is_empty() {
+        if str.is_empty() {
             bail!("RustPath can't be empty");
         }
-        if parts.iter().any(String::is_empty) {
-            bail!("RustPath item can't be empty");
-        }
-        Ok(RustPath { parts })
+        RustPath::try_from_parts(str.split("::").map(String::from).collect())
+    }
+}
+
+impl fmt::Display for RustPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.parts.join("::"))
     }
 }
 
@@ -43,11 +81,21 @@ impl PartialEq<&str> for RustPath {
 
 impl RustPath {
     /// Creates new `RustPath` consisting of `parts`.
+    /// Panics if `parts` is empty or contains an empty string.
     pub fn from_parts(parts: Vec<String>) -> Self {
+        Self::try_from_parts(parts).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates new `RustPath` consisting of `parts`.
+    /// Returns an error if `parts` is empty or contains an empty string.
+    pub fn try_from_parts(parts: Vec<String>) -> Result<Self> {
         if parts.is_empty() {
-            panic!("RustPath can't be empty");
+            bail!("RustPath can't be empty");
+        }
+        if parts.iter().any(String::is_empty) {
+            bail!("RustPath item can't be empty: {:?}", parts);
         }
-        RustPath { parts }
+        Ok(RustPath { parts })
     }
 
     pub fn from_good_str(str: &str) -> Self {
@@ -71,6 +119,7 @@ impl RustPath {
         self.parts.last_mut().expect("RustPath can't be empty")
     }
 
+    /// Returns a child path with `name` appended to this path.
     pub fn join(&self, name: impl Into<String>) -> RustPath {
         let mut new_path = self.clone();
         new_path.parts.push(name.into());
@@ -90,6 +139,32 @@ impl RustPath {
         format!("::{}", self.parts.join("::"))
     }
 
+    /// Same as `full_name`, but escapes Rust keywords in the path.
+    /// The last part is escaped with `escape_rust_identifier` (e.g. `r#type`).
+    /// Intermediate parts are module names, so they are sanitized the same way
+    /// the generator names modules (e.g. `type_`, `lib_`). This function should be used
+    /// for code generation, while `full_name` is suitable for diagnostics.
+    pub fn to_rust_code(&self, current_crate: Option<&str>) -> String {
+        let last_index = self.parts.len() - 1;
+        let parts = self.parts.iter().enumerate().map(|(index, part)| {
+            if index == 0 {
+                part.clone()
+            } else if index == last_index {
+                escape_rust_identifier(part)
+            } else {
+                sanitize_rust_identifier(part, true)
+            }
+        });
+
+        if let Some(current_crate) = current_crate {
+            if self.crate_name() == current_crate {
+                return format!("crate::{}", parts.skip(1).join("::"));
+            }
+        }
+
+        format!("::{}", parts.join("::"))
+    }
+
     /// Returns true if `other` is nested within `self`.
     pub fn includes(&self, other: &RustPath) -> bool {
         let extra_modules_count = other.parts.len() as isize - self.parts.len() as isize;
@@ -106,6 +181,8 @@ impl RustPath {
         parent.includes_directly(self)
     }
 
+    /// Returns the path without its last part.
+    /// Returns an error if the path consists of a single part.
     pub fn parent(&self) -> Result<RustPath> {
         if self.parts.len() > 1 {
             let mut new_path = self.clone();
@@ -127,6 +204,62 @@ impl RustPath {
     pub fn parts(&self) -> &[String] {
         &self.parts
     }
+
+    /// Returns true if `self` and `other` have the same parts
+    /// if ASCII case is ignored.
+    pub fn eq_ignore_ascii_case(&self, other: &RustPath) -> bool {
+        self.parts.len() == other.parts.len()
+            && self
+                .parts
+                .iter()
+                .zip(&other.parts)
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+
+    /// Returns a copy of this path with the last part converted to snake case
+    /// (e.g. `QString` -> `q_string`). Other parts are left unchanged.
+    pub fn to_snake_case(&self) -> RustPath {
+        let mut new_path = self.clone();
+        *new_path.last_mut() = self.last().to_snake_case();
+        new_path
+    }
+
+    /// Returns a copy of this path with the last part converted to upper camel case
+    /// (e.g. `to_html` -> `ToHtml`). Other parts are left unchanged.
+    pub fn to_upper_camel_case(&self) -> RustPath {
+        let mut new_path = self.clone();
+        *new_path.last_mut() = self.last().to_class_case();
+        new_path
+    }
+
+    /// Returns a copy of this path with a numeric suffix added to the last part
+    /// (`Foo2`, `Foo3`, ...) if the last part is in `taken`.
+    /// The smallest suffix that makes the name unique is used.
+    /// Returns an unchanged copy if the last part is not taken.
+    pub fn disambiguate(&self, taken: &HashSet<String>) -> RustPath {
+        let mut new_path = self.clone();
+        let mut index = 2;
+        while taken.contains(new_path.last()) {
+            *new_path.last_mut() = format!("{}{}", self.last(), index);
+            index += 1;
+        }
+        new_path
+    }
+}
+
+/// Returns all pairs of distinct paths from `paths` that are only different in
+/// ASCII case. Such paths clash on case-insensitive file systems and linkers
+/// (e.g. on Windows).
+pub fn case_insensitive_clashes(paths: &[RustPath]) -> Vec<(&RustPath, &RustPath)> {
+    let mut clashes = Vec::new();
+    for (index, path) in paths.iter().enumerate() {
+        for other in &paths[index + 1..] {
+            if path != other && path.eq_ignore_ascii_case(other) {
+                clashes.push((path, other));
+            }
+        }
+    }
+    clashes
 }
 
 /// Conversion from public Rust API type to
@@ -154,6 +287,11 @@ pub enum RustToFfiTypeConversion {
     /// `QBox<T>` to `*mut T`
     QBoxToPtr,
     /// `qt_core::flags::Flags<T>` to `c_int`
+    ///
+    /// The conversion works in both directions: arguments are converted
+    /// using `to_int()`, and return values are converted back using
+    /// `QFlags::from(x)`. `api_type` must be the complete `QFlags<T>` type
+    /// because the enum type `T` can't be deduced from the FFI type.
     QFlagsToUInt {
         api_type: RustType,
     },
@@ -163,6 +301,16 @@ pub enum RustToFfiTypeConversion {
     AsCast {
         api_type: RustType,
     },
+    /// `&str` to `*const c_char`.
+    ///
+    /// The string is copied into a temporary null-terminated `CString`
+    /// that is only valid until the FFI call returns, so the C++ side
+    /// must not store the pointer. The conversion panics if the string
+    /// contains an interior nul byte. Only supported in argument position.
+    CStrToPtr,
+    /// `bool` to an integer type (e.g. `c_int`). Converted using `as`
+    /// in argument position and `!= 0` in return position.
+    BoolToInt,
     /// Rust public type has an additional reference (`&`)
     RefTo(Box<RustToFfiTypeConversion>),
     ImplCastInto(Box<RustToFfiTypeConversion>),
@@ -243,6 +391,34 @@ fn class_type_to_qt_core_crate_path(t: &RustType) -> Result<RustPath> {
     }
 }
 
+fn is_integer_type(t: &RustType) -> bool {
+    match t {
+        RustType::Primitive(name) => [
+            "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+        ]
+        .contains(&name.as_str()),
+        RustType::Common(RustCommonType {
+            path,
+            generic_arguments: None,
+        }) => [
+            "std::os::raw::c_char",
+            "std::os::raw::c_schar",
+            "std::os::raw::c_uchar",
+            "std::os::raw::c_short",
+            "std::os::raw::c_ushort",
+            "std::os::raw::c_int",
+            "std::os::raw::c_uint",
+            "std::os::raw::c_long",
+            "std::os::raw::c_ulong",
+            "std::os::raw::c_longlong",
+            "std::os::raw::c_ulonglong",
+        ]
+        .iter()
+        .any(|name| path == name),
+        _ => false,
+    }
+}
+
 impl RustFinalType {
     pub fn new(ffi_type: RustType, api_to_ffi_conversion: RustToFfiTypeConversion) -> Result<Self> {
         let api_type = match &api_to_ffi_conversion {
@@ -290,6 +466,11 @@ impl RustFinalType {
             RustToFfiTypeConversion::QFlagsToUInt { api_type }
             | RustToFfiTypeConversion::AsCast { api_type } => api_type.clone(),
             RustToFfiTypeConversion::UnitToAnything => RustType::unit(),
+            RustToFfiTypeConversion::CStrToPtr => {
+                ffi_type.pointer_like_to_target()?;
+                RustType::new_reference(true, RustType::Primitive("str".into()))
+            }
+            RustToFfiTypeConversion::BoolToInt => RustType::Primitive("bool".into()),
             RustToFfiTypeConversion::RefTo(conversion) => {
                 let intermediate = RustFinalType::new(ffi_type.clone(), (**conversion).clone())?;
                 RustType::new_reference(true, intermediate.api_type)
@@ -325,6 +506,82 @@ impl RustFinalType {
         &self.conversion
     }
 
+    /// Returns a description of this type for generated documentation
+    /// that includes the original C++ type `cpp_type`,
+    /// e.g. ``Rust `&QString` (C++ `const QString&`)``.
+    pub fn doc_signature(&self, cpp_type: &CppType, current_crate: Option<&str>) -> String {
+        format!(
+            "Rust `{}` (C++ `{}`)",
+            self.api_type.to_rust_code(current_crate),
+            cpp_type.to_cpp_pseudo_code()
+        )
+    }
+
+    /// Checks that `conversion` is compatible with `api_type` and `ffi_type`
+    /// and returns an error describing the mismatch otherwise.
+    pub fn validate(&self) -> Result<()> {
+        let expected_api_type =
+            RustFinalType::new(self.ffi_type.clone(), self.conversion.clone())?.api_type;
+        if self.api_type != expected_api_type {
+            bail!(
+                "API type {} doesn't match conversion {:?} (expected {})",
+                self.api_type,
+                self.conversion,
+                expected_api_type
+            );
+        }
+        match &self.conversion {
+            RustToFfiTypeConversion::RefToPtr { .. } => {
+                if !self.api_type.is_ref() {
+                    bail!(
+                        "RefToPtr requires a reference API type, got {}",
+                        self.api_type
+                    );
+                }
+                if !self.ffi_type.is_ptr() {
+                    bail!(
+                        "RefToPtr requires a pointer FFI type, got {}",
+                        self.ffi_type
+                    );
+                }
+            }
+            RustToFfiTypeConversion::UtilsPtrToPtr {}
+            | RustToFfiTypeConversion::UtilsRefToPtr {}
+            | RustToFfiTypeConversion::OptionUtilsRefToPtr {}
+            | RustToFfiTypeConversion::QPtrToPtr
+            | RustToFfiTypeConversion::ValueToPtr
+            | RustToFfiTypeConversion::CppBoxToPtr
+            | RustToFfiTypeConversion::QBoxToPtr
+            | RustToFfiTypeConversion::CStrToPtr => {
+                if !self.ffi_type.is_ptr() {
+                    bail!(
+                        "{:?} requires a pointer FFI type, got {}",
+                        self.conversion,
+                        self.ffi_type
+                    );
+                }
+            }
+            RustToFfiTypeConversion::QFlagsToUInt { .. } | RustToFfiTypeConversion::BoolToInt => {
+                if !is_integer_type(&self.ffi_type) {
+                    bail!(
+                        "{:?} requires an integer FFI type, got {}",
+                        self.conversion,
+                        self.ffi_type
+                    );
+                }
+            }
+            RustToFfiTypeConversion::RefTo(conversion)
+            | RustToFfiTypeConversion::ImplCastInto(conversion) => {
+                RustFinalType::new(self.ffi_type.clone(), (**conversion).clone())?.validate()?;
+            }
+            RustToFfiTypeConversion::None
+            | RustToFfiTypeConversion::UnitToAnything
+            | RustToFfiTypeConversion::AsCast { .. }
+            | RustToFfiTypeConversion::ClosureToCallback(_) => {}
+        }
+        Ok(())
+    }
+
     pub fn with_lifetime(&self, lifetime: String) -> Result<Self> {
         if let RustToFfiTypeConversion::RefToPtr { .. } = &self.conversion {
             RustFinalType::new(
@@ -363,6 +620,18 @@ impl RustPointerLikeTypeKind {
     }
 }
 
+/// Kind of the pointer holding a trait object.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum RustTraitObjectKind {
+    /// `&'lifetime dyn Trait` or `&'lifetime mut dyn Trait`
+    Reference {
+        lifetime: Option<String>,
+        is_const: bool,
+    },
+    /// `Box<dyn Trait>`
+    Box,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct RustCommonType {
     /// Full name of the base type
@@ -424,7 +693,127 @@ where
     false
 }
 
+/// Adds lifetimes of all references contained in `rust_type` to `output`.
+/// `None` is added for references without a lifetime.
+fn collect_reference_lifetimes<'a>(rust_type: &'a RustType, output: &mut Vec<Option<&'a str>>) {
+    match rust_type {
+        RustType::Tuple(types) => {
+            for t in types {
+                collect_reference_lifetimes(t, output);
+            }
+        }
+        RustType::Common(common) | RustType::ImplTrait(common) => {
+            for arg in common.generic_arguments.iter().flatten() {
+                collect_reference_lifetimes(arg, output);
+            }
+        }
+        RustType::PointerLike { kind, target, .. } => {
+            if let RustPointerLikeTypeKind::Reference { lifetime } = kind {
+                output.push(lifetime.as_ref().map(String::as_str));
+            }
+            collect_reference_lifetimes(target, output);
+        }
+        RustType::TraitObject { kind, trait_type } => {
+            if let RustTraitObjectKind::Reference { lifetime, .. } = kind {
+                output.push(lifetime.as_ref().map(String::as_str));
+            }
+            for arg in trait_type.generic_arguments.iter().flatten() {
+                collect_reference_lifetimes(arg, output);
+            }
+        }
+        RustType::Slice(item) | RustType::Array { item, .. } => {
+            collect_reference_lifetimes(item, output);
+        }
+        // lifetimes of function pointer types are not affected by elision rules
+        RustType::FunctionPointer(_) | RustType::Primitive(_) | RustType::GenericParameter(_) => {}
+    }
+}
+
+/// Returns true if all explicit lifetimes in a function signature with
+/// `arguments` and `return_type` can be omitted according
+/// to Rust's lifetime elision rules without changing its meaning.
+///
+/// This is possible if all lifetimes of the arguments are distinct, and
+/// the return type either doesn't contain references or only
+/// uses the lifetime of the single argument reference.
+pub fn can_elide_lifetimes(arguments: &[RustType], return_type: &RustType) -> bool {
+    let input = arguments
+        .iter()
+        .flat_map(RustType::reference_lifetimes)
+        .collect_vec();
+    let output = return_type.reference_lifetimes();
+
+    if input.iter().chain(&output).any(|l| *l == Some("static")) {
+        return false;
+    }
+    let named_input = input.iter().filter_map(|l| *l).collect_vec();
+    if named_input.iter().unique().count() != named_input.len() {
+        // a lifetime is shared between multiple arguments
+        return false;
+    }
+    if output.is_empty() {
+        return true;
+    }
+    if input.len() != 1 {
+        return false;
+    }
+    output.iter().all(|l| l.is_none() || *l == input[0])
+}
+
+/// Same as `can_elide_lifetimes`, but for a method with a `&self` or `&mut self`
+/// receiver with `self_lifetime` (`None` if it's not named) and other `arguments`.
+///
+/// According to the elision rules, references in the return type of such a method
+/// take the lifetime of `self`, so the return type may only use `self_lifetime`.
+pub fn can_elide_method_lifetimes(
+    self_lifetime: Option<&str>,
+    arguments: &[RustType],
+    return_type: &RustType,
+) -> bool {
+    let input = iter::once(self_lifetime)
+        .chain(arguments.iter().flat_map(RustType::reference_lifetimes))
+        .collect_vec();
+    let output = return_type.reference_lifetimes();
+
+    if input.iter().chain(&output).any(|l| *l == Some("static")) {
+        return false;
+    }
+    let named_input = input.iter().filter_map(|l| *l).collect_vec();
+    if named_input.iter().unique().count() != named_input.len() {
+        return false;
+    }
+    output.iter().all(|l| l.is_none() || *l == self_lifetime)
+}
+
 impl RustCommonType {
+    /// Returns Rust code representing this type, including generic arguments.
+    /// If `current_crate` is `None`, it's assumed that the code
+    /// will be used outside of the crate the type belongs to.
+    pub fn to_rust_code(&self, current_crate: Option<&str>) -> String {
+        let mut code = self.path.to_rust_code(current_crate);
+        if let Some(args) = &self.generic_arguments {
+            code.push_str(&format!(
+                "<{}>",
+                args.iter()
+                    .map(|x| x.to_rust_code(current_crate))
+                    .join(", ")
+            ));
+        }
+        code
+    }
+
+    /// Returns a copy of this type with lifetimes removed from all references
+    /// within generic arguments.
+    pub fn without_lifetimes(&self) -> RustCommonType {
+        RustCommonType {
+            path: self.path.clone(),
+            generic_arguments: self
+                .generic_arguments
+                .as_ref()
+                .map(|args| args.iter().map(RustType::without_lifetimes).collect()),
+        }
+    }
+
     pub fn can_be_same_as(&self, other: &RustCommonType) -> bool {
         let self_args = self
             .generic_arguments
@@ -458,6 +847,8 @@ pub struct RustFunctionPointerType {
 /// A Rust type
 #[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum RustType {
+    /// Tuple type `(A, B, ...)`. The empty tuple `()` is the unit type
+    /// used as the replacement of C++'s `void`.
     Tuple(Vec<RustType>),
     /// Primitive built-in type (`i32`, `str`, etc.)
     Primitive(String),
@@ -465,13 +856,34 @@ pub enum RustType {
     Common(RustCommonType),
     /// A function pointer type.
     FunctionPointer(RustFunctionPointerType),
+    /// Raw pointer (`*const T`, `*mut T`) or reference (`&T`, `&mut T`).
     PointerLike {
         kind: RustPointerLikeTypeKind,
+        /// Mutability of the pointer or reference: `*const T` and `&T` if true,
+        /// `*mut T` and `&mut T` otherwise.
         is_const: bool,
         target: Box<RustType>,
     },
     ImplTrait(RustCommonType),
+    /// Trait object behind a reference or a box (`&dyn Trait`, `Box<dyn Trait>`).
+    TraitObject {
+        kind: RustTraitObjectKind,
+        trait_type: RustCommonType,
+    },
     GenericParameter(String),
+    /// Slice type `[T]`. Usually used behind a reference (`&[T]` or `&mut [T]`).
+    Slice(Box<RustType>),
+    /// Fixed-size array type `[T; N]`.
+    Array {
+        item: Box<RustType>,
+        len: usize,
+    },
+}
+
+impl fmt::Display for RustType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_rust_code(None))
+    }
 }
 
 impl RustType {
@@ -500,6 +912,11 @@ impl RustType {
         }
     }
 
+    /// Constructs a slice reference type (`&[T]` or `&mut [T]`).
+    pub fn new_slice_reference(is_const: bool, item: RustType) -> Self {
+        RustType::new_reference(is_const, RustType::Slice(Box::new(item)))
+    }
+
     pub fn new_option(target: RustType) -> Self {
         RustType::Common(RustCommonType {
             path: RustPath::from_good_str("std::option::Option"),
@@ -507,6 +924,167 @@ impl RustType {
         })
     }
 
+    /// Constructs a nullable reference type (`Option<&T>` or `Option<&mut T>`).
+    pub fn new_nullable_reference(is_const: bool, target: RustType) -> Self {
+        RustType::new_option(RustType::new_reference(is_const, target))
+    }
+
+    /// Checks that `Option` is only used as a nullable pointer in this type and its
+    /// generic arguments, i.e. its argument is a pointer, a reference, or `cpp_core::Ref`.
+    pub fn validate_option(&self) -> Result<()> {
+        if let Some(arg) = self.option_argument() {
+            if !arg.can_be_option_argument() {
+                bail!("Option must contain a pointer or a reference, got {}", self);
+            }
+        }
+        match self {
+            RustType::Common(RustCommonType {
+                generic_arguments: Some(args),
+                ..
+            }) => {
+                for arg in args {
+                    arg.validate_option()?;
+                }
+            }
+            RustType::PointerLike { target, .. } => target.validate_option()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns Rust code representing this type.
+    /// If `current_crate` is `None`, it's assumed that the code
+    /// will be used outside of the crate the type belongs to.
+    pub fn to_rust_code(&self, current_crate: Option<&str>) -> String {
+        match self {
+            RustType::Tuple(types) => {
+                let types_text = types
+                    .iter()
+                    .map(|t| t.to_rust_code(current_crate))
+                    .join(", ");
+                if types.len() == 1 {
+                    format!("({},)", types_text)
+                } else {
+                    format!("({})", types_text)
+                }
+            }
+            RustType::Primitive(type1) | RustType::GenericParameter(type1) => type1.to_string(),
+            RustType::PointerLike {
+                kind,
+                target,
+                is_const,
+            } => {
+                let target_code = target.to_rust_code(current_crate);
+                match kind {
+                    RustPointerLikeTypeKind::Pointer => {
+                        if *is_const {
+                            format!("*const {}", target_code)
+                        } else {
+                            format!("*mut {}", target_code)
+                        }
+                    }
+                    RustPointerLikeTypeKind::Reference { lifetime } => {
+                        let lifetime_text = match lifetime {
+                            Some(lifetime) => format!("'{} ", lifetime),
+                            None => String::new(),
+                        };
+                        if *is_const {
+                            format!("&{}{}", lifetime_text, target_code)
+                        } else {
+                            format!("&{}mut {}", lifetime_text, target_code)
+                        }
+                    }
+                }
+            }
+            RustType::Common(common) => common.to_rust_code(current_crate),
+            RustType::FunctionPointer(function) => format!(
+                "extern \"C\" fn({}){}",
+                function
+                    .arguments
+                    .iter()
+                    .map(|arg| arg.to_rust_code(current_crate))
+                    .join(", "),
+                if function.return_type.is_unit() {
+                    String::new()
+                } else {
+                    format!(" -> {}", function.return_type.to_rust_code(current_crate))
+                }
+            ),
+            RustType::ImplTrait(trait_type) => {
+                format!("impl {}", trait_type.to_rust_code(current_crate))
+            }
+            RustType::TraitObject { kind, trait_type } => {
+                let trait_code = trait_type.to_rust_code(current_crate);
+                match kind {
+                    RustTraitObjectKind::Reference { lifetime, is_const } => {
+                        let lifetime_text = match lifetime {
+                            Some(lifetime) => format!("'{} ", lifetime),
+                            None => String::new(),
+                        };
+                        if *is_const {
+                            format!("&{}dyn {}", lifetime_text, trait_code)
+                        } else {
+                            format!("&{}mut dyn {}", lifetime_text, trait_code)
+                        }
+                    }
+                    RustTraitObjectKind::Box => format!("::std::boxed::Box<dyn {}>", trait_code),
+                }
+            }
+            RustType::Slice(item) => format!("[{}]", item.to_rust_code(current_crate)),
+            RustType::Array { item, len } => {
+                format!("[{}; {}]", item.to_rust_code(current_crate), len)
+            }
+        }
+    }
+
+    /// Returns true if this type is a pointer, a reference, or `cpp_core::Ref`,
+    /// so it can be made nullable by wrapping in `Option`.
+    fn can_be_option_argument(&self) -> bool {
+        match self {
+            RustType::PointerLike { .. } => true,
+            RustType::Common(RustCommonType { path, .. }) => {
+                path == &RustPath::from_good_str("cpp_core::Ref")
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns this type wrapped in `Option`, or this type unchanged
+    /// if it's already an `Option`.
+    ///
+    /// Panics if this type is not a pointer, a reference, or `cpp_core::Ref`.
+    pub fn into_option(self) -> RustType {
+        if self.option_argument().is_some() {
+            return self;
+        }
+        assert!(
+            self.can_be_option_argument(),
+            "only pointers and references can be wrapped in Option, got {}",
+            self
+        );
+        RustType::new_option(self)
+    }
+
+    /// Returns the argument of this type if it's an `Option`,
+    /// or a copy of this type otherwise.
+    pub fn strip_option(&self) -> RustType {
+        self.option_argument().unwrap_or(self).clone()
+    }
+
+    /// If this type is `Option<T>`, returns `T`. Returns `None` otherwise.
+    pub fn option_argument(&self) -> Option<&RustType> {
+        if let RustType::Common(RustCommonType {
+            path,
+            generic_arguments: Some(args),
+        }) = self
+        {
+            if path == &RustPath::from_good_str("std::option::Option") && args.len() == 1 {
+                return Some(&args[0]);
+            }
+        }
+        None
+    }
+
     pub fn is_unit(&self) -> bool {
         if let RustType::Tuple(types) = self {
             types.is_empty()
@@ -515,14 +1093,169 @@ impl RustType {
         }
     }
 
+    /// Returns source code of an expression producing a default value of this type
+    /// (`0` for integers, `false` for `bool`, null for raw pointers, `None` for `Option`,
+    /// etc.). Returns `None` if there is no obvious default value.
+    pub fn default_expression(&self) -> Option<String> {
+        if is_integer_type(self) {
+            return Some("0".to_string());
+        }
+        let text = match self {
+            RustType::Tuple(types) if types.is_empty() => "()",
+            RustType::Primitive(name) => match name.as_str() {
+                "f32" | "f64" => "0.0",
+                "bool" => "false",
+                _ => return None,
+            },
+            RustType::Common(RustCommonType { path, .. }) => {
+                if path == &RustPath::from_good_str("std::os::raw::c_float")
+                    || path == &RustPath::from_good_str("std::os::raw::c_double")
+                {
+                    "0.0"
+                } else if path == &RustPath::from_good_str("std::option::Option") {
+                    "None"
+                } else if path == &RustPath::from_good_str("std::vec::Vec") {
+                    "Vec::new()"
+                } else if path == &RustPath::from_good_str("std::string::String") {
+                    "String::new()"
+                } else {
+                    return None;
+                }
+            }
+            RustType::PointerLike {
+                kind: RustPointerLikeTypeKind::Pointer,
+                is_const,
+                ..
+            } => {
+                if *is_const {
+                    "::std::ptr::null()"
+                } else {
+                    "::std::ptr::null_mut()"
+                }
+            }
+            _ => return None,
+        };
+        Some(text.to_string())
+    }
+
+    /// Returns true if this type can be used in an `extern "C"` function signature.
+    ///
+    /// Raw pointers, `()`, primitive types (except `str`, `char`, `i128` and `u128`,
+    /// which have no stable C ABI), `std::os::raw` types,
+    /// non-generic types (assumed to be `#[repr(C)]`) and function pointers
+    /// with FFI-safe signatures are allowed. References are only allowed as
+    /// `Option<&T>` (a nullable pointer). `Option` of a value, `Vec`
+    /// and other generic or `std` types are not FFI-safe.
+    pub fn is_ffi_safe(&self) -> bool {
+        match self {
+            RustType::Tuple(types) => types.is_empty(),
+            RustType::Primitive(name) => match name.as_str() {
+                "str" | "char" | "i128" | "u128" => false,
+                _ => true,
+            },
+            RustType::Common(RustCommonType {
+                path,
+                generic_arguments,
+            }) => {
+                if let Some(arg) = self.option_argument() {
+                    return arg.is_ref();
+                }
+                if path.crate_name() == "std" {
+                    return path.is_child_of(&RustPath::from_good_str("std::os::raw"));
+                }
+                generic_arguments.is_none()
+            }
+            RustType::FunctionPointer(function) => {
+                function.return_type.is_ffi_safe()
+                    && function.arguments.iter().all(RustType::is_ffi_safe)
+            }
+            RustType::PointerLike { kind, .. } => kind.is_pointer(),
+            RustType::ImplTrait(_)
+            | RustType::TraitObject { .. }
+            | RustType::GenericParameter(_)
+            | RustType::Slice(_)
+            | RustType::Array { .. } => false,
+        }
+    }
+
+    /// Returns size and alignment (in bytes) of this type on the current platform
+    /// if it's a primitive type, a `std::os::raw` type or a thin raw pointer.
+    /// Returns `None` for all other types.
+    pub fn primitive_layout(&self) -> Option<(usize, usize)> {
+        fn layout<T>() -> Option<(usize, usize)> {
+            Some((mem::size_of::<T>(), mem::align_of::<T>()))
+        }
+
+        match self {
+            RustType::Primitive(name) => match name.as_str() {
+                "bool" => layout::<bool>(),
+                "char" => layout::<char>(),
+                "i8" => layout::<i8>(),
+                "u8" => layout::<u8>(),
+                "i16" => layout::<i16>(),
+                "u16" => layout::<u16>(),
+                "i32" => layout::<i32>(),
+                "u32" => layout::<u32>(),
+                "i64" => layout::<i64>(),
+                "u64" => layout::<u64>(),
+                "i128" => layout::<i128>(),
+                "u128" => layout::<u128>(),
+                "isize" => layout::<isize>(),
+                "usize" => layout::<usize>(),
+                "f32" => layout::<f32>(),
+                "f64" => layout::<f64>(),
+                _ => None,
+            },
+            RustType::Common(RustCommonType {
+                path,
+                generic_arguments: None,
+            }) if path.is_child_of(&RustPath::from_good_str("std::os::raw")) => match path.last() {
+                "c_char" => layout::<raw::c_char>(),
+                "c_schar" => layout::<raw::c_schar>(),
+                "c_uchar" => layout::<raw::c_uchar>(),
+                "c_short" => layout::<raw::c_short>(),
+                "c_ushort" => layout::<raw::c_ushort>(),
+                "c_int" => layout::<raw::c_int>(),
+                "c_uint" => layout::<raw::c_uint>(),
+                "c_long" => layout::<raw::c_long>(),
+                "c_ulong" => layout::<raw::c_ulong>(),
+                "c_longlong" => layout::<raw::c_longlong>(),
+                "c_ulonglong" => layout::<raw::c_ulonglong>(),
+                "c_float" => layout::<raw::c_float>(),
+                "c_double" => layout::<raw::c_double>(),
+                _ => None,
+            },
+            RustType::PointerLike {
+                kind: RustPointerLikeTypeKind::Pointer,
+                target,
+                ..
+            } => match &**target {
+                // pointers to dynamically sized types are fat pointers
+                RustType::Slice(_) | RustType::ImplTrait(_) => None,
+                RustType::Primitive(name) if name == "str" => None,
+                _ => layout::<*const u8>(),
+            },
+            _ => None,
+        }
+    }
+
     /// Returns alphanumeric description of this type
     /// for purposes of name disambiguation.
     pub fn caption(&self, context: &RustPath, strategy: RustTypeCaptionStrategy) -> Result<String> {
         Ok(match self {
-            RustType::Tuple(types) => types
-                .iter()
-                .map_if_ok(|t| t.caption(context, strategy))?
-                .join("_"),
+            RustType::Tuple(types) => {
+                if types.is_empty() {
+                    String::new()
+                } else {
+                    format!(
+                        "tuple_{}",
+                        types
+                            .iter()
+                            .map_if_ok(|t| t.caption(context, strategy))?
+                            .join("_")
+                    )
+                }
+            }
             RustType::Primitive(type1) | RustType::GenericParameter(type1) => type1.to_string(),
             RustType::PointerLike { target, .. } => {
                 // let const_text = if *is_const { "_const" } else { "" };
@@ -588,13 +1321,17 @@ impl RustType {
                         "{}_{}",
                         name,
                         args.iter()
-                            .map_if_ok(|x| x.caption(context, strategy))?
+                            .map_if_ok(|x| x.caption_or_void(context, strategy))?
                             .join("_")
                     );
                 }
                 name
             }
             RustType::FunctionPointer { .. } => "fn".to_string(),
+            RustType::Slice(item) => format!("{}_slice", item.caption(context, strategy)?),
+            RustType::Array { item, len } => {
+                format!("{}_array_{}", item.caption(context, strategy)?, len)
+            }
             RustType::ImplTrait(trait_type) => {
                 if trait_type.path == RustPath::from_good_str("cpp_core::CastInto") {
                     trait_type
@@ -607,9 +1344,27 @@ impl RustType {
                     RustType::Common(trait_type.clone()).caption(context, strategy)?
                 }
             }
+            RustType::TraitObject { trait_type, .. } => format!(
+                "dyn_{}",
+                RustType::Common(trait_type.clone()).caption(context, strategy)?
+            ),
         })
     }
 
+    /// Same as `caption`, but returns `"void"` for the unit type
+    /// instead of an empty string.
+    pub fn caption_or_void(
+        &self,
+        context: &RustPath,
+        strategy: RustTypeCaptionStrategy,
+    ) -> Result<String> {
+        if self.is_unit() {
+            Ok("void".to_string())
+        } else {
+            self.caption(context, strategy)
+        }
+    }
+
     /// Returns true if this type is a reference.
     pub fn is_ref(&self) -> bool {
         match self {
@@ -618,29 +1373,288 @@ impl RustType {
         }
     }
 
+    /// Returns true if this type is a raw pointer.
+    pub fn is_ptr(&self) -> bool {
+        match self {
+            RustType::PointerLike { kind, .. } => kind.is_pointer(),
+            _ => false,
+        }
+    }
+
+    /// Returns true if this type is a const raw pointer or an immutable reference.
+    /// Returns false for all other types.
+    pub fn is_const(&self) -> bool {
+        match self {
+            RustType::PointerLike { is_const, .. } => *is_const,
+            _ => false,
+        }
+    }
+
     /// Returns a copy of this type with `new_lifetime` added, if possible.
     pub fn with_lifetime(&self, new_lifetime: String) -> RustType {
         let mut r = self.clone();
-        if let RustType::PointerLike { kind, .. } = &mut r {
-            match kind {
-                RustPointerLikeTypeKind::Pointer => {}
-                RustPointerLikeTypeKind::Reference { lifetime } => {
-                    *lifetime = Some(new_lifetime);
-                }
+        match &mut r {
+            RustType::PointerLike {
+                kind: RustPointerLikeTypeKind::Reference { lifetime },
+                ..
             }
+            | RustType::TraitObject {
+                kind: RustTraitObjectKind::Reference { lifetime, .. },
+                ..
+            } => {
+                *lifetime = Some(new_lifetime);
+            }
+            _ => {}
         }
         r
     }
 
+    /// Returns a copy of this type with `new_lifetime` assigned to all references
+    /// (including references within generic arguments) that don't have a lifetime.
+    /// Existing lifetimes are preserved. Unlike `with_lifetime`, this also works
+    /// for value types, e.g. `Vec<&T>`.
+    pub fn with_lifetime_deep(&self, new_lifetime: &str) -> RustType {
+        self.map_lifetimes(&|lifetime| Some(lifetime.unwrap_or(new_lifetime).to_string()))
+    }
+
+    /// Returns lifetimes of all references contained in this type
+    /// (including generic arguments) in order of appearance.
+    /// `None` is returned for references without a lifetime.
+    pub fn reference_lifetimes(&self) -> Vec<Option<&str>> {
+        let mut r = Vec::new();
+        collect_reference_lifetimes(self, &mut r);
+        r
+    }
+
+    /// Returns a copy of this type with lifetimes of all references
+    /// (including generic arguments) replaced with the output of `f`.
+    /// `f` receives the current lifetime of each reference.
+    fn map_lifetimes<F: Fn(Option<&str>) -> Option<String>>(&self, f: &F) -> RustType {
+        let map_common = |common: &RustCommonType| RustCommonType {
+            path: common.path.clone(),
+            generic_arguments: common
+                .generic_arguments
+                .as_ref()
+                .map(|args| args.iter().map(|arg| arg.map_lifetimes(f)).collect()),
+        };
+        match self {
+            RustType::Tuple(types) => {
+                RustType::Tuple(types.iter().map(|t| t.map_lifetimes(f)).collect())
+            }
+            RustType::Common(common) => RustType::Common(map_common(common)),
+            RustType::ImplTrait(common) => RustType::ImplTrait(map_common(common)),
+            RustType::PointerLike {
+                kind,
+                is_const,
+                target,
+            } => RustType::PointerLike {
+                kind: match kind {
+                    RustPointerLikeTypeKind::Pointer => RustPointerLikeTypeKind::Pointer,
+                    RustPointerLikeTypeKind::Reference { lifetime } => {
+                        RustPointerLikeTypeKind::Reference {
+                            lifetime: f(lifetime.as_ref().map(String::as_str)),
+                        }
+                    }
+                },
+                is_const: *is_const,
+                target: Box::new(target.map_lifetimes(f)),
+            },
+            RustType::TraitObject { kind, trait_type } => RustType::TraitObject {
+                kind: match kind {
+                    RustTraitObjectKind::Reference { lifetime, is_const } => {
+                        RustTraitObjectKind::Reference {
+                            lifetime: f(lifetime.as_ref().map(String::as_str)),
+                            is_const: *is_const,
+                        }
+                    }
+                    RustTraitObjectKind::Box => RustTraitObjectKind::Box,
+                },
+                trait_type: map_common(trait_type),
+            },
+            RustType::Slice(item) => RustType::Slice(Box::new(item.map_lifetimes(f))),
+            RustType::Array { item, len } => RustType::Array {
+                item: Box::new(item.map_lifetimes(f)),
+                len: *len,
+            },
+            RustType::FunctionPointer(_)
+            | RustType::Primitive(_)
+            | RustType::GenericParameter(_) => self.clone(),
+        }
+    }
+
+    /// Adds all paths used within this type (including generic arguments)
+    /// to `output`.
+    fn collect_paths<'a>(&'a self, output: &mut Vec<&'a RustPath>) {
+        match self {
+            RustType::Tuple(types) => {
+                for t in types {
+                    t.collect_paths(output);
+                }
+            }
+            RustType::Common(common)
+            | RustType::ImplTrait(common)
+            | RustType::TraitObject {
+                trait_type: common, ..
+            } => {
+                output.push(&common.path);
+                for arg in common.generic_arguments.iter().flatten() {
+                    arg.collect_paths(output);
+                }
+            }
+            RustType::FunctionPointer(function) => {
+                function.return_type.collect_paths(output);
+                for arg in &function.arguments {
+                    arg.collect_paths(output);
+                }
+            }
+            RustType::PointerLike { target, .. } => target.collect_paths(output),
+            RustType::Slice(item) | RustType::Array { item, .. } => item.collect_paths(output),
+            RustType::Primitive(_) | RustType::GenericParameter(_) => {}
+        }
+    }
+
+    /// Returns names of all crates referenced by this type (including generic
+    /// arguments), except `current_crate`.
+    pub fn referenced_crates(&self, current_crate: Option<&str>) -> BTreeSet<String> {
+        let mut paths = Vec::new();
+        self.collect_paths(&mut paths);
+        paths
+            .into_iter()
+            .map(RustPath::crate_name)
+            .filter(|name| Some(*name) != current_crate)
+            .map(String::from)
+            .collect()
+    }
+
+    /// Returns a copy of this type rewritten by `f` bottom-up.
+    ///
+    /// All nested types (generic arguments, pointer targets, tuple items,
+    /// function pointer arguments, etc.) are folded first. Then `f` is called
+    /// with the current type containing the folded nested types. If `f` returns
+    /// `Some`, the returned type replaces the current type; otherwise,
+    /// the current type is kept.
+    pub fn fold(&self, f: &mut impl FnMut(&RustType) -> Option<RustType>) -> RustType {
+        fn fold_common(
+            common: &RustCommonType,
+            f: &mut impl FnMut(&RustType) -> Option<RustType>,
+        ) -> RustCommonType {
+            RustCommonType {
+                path: common.path.clone(),
+                generic_arguments: common
+                    .generic_arguments
+                    .as_ref()
+                    .map(|args| args.iter().map(|arg| arg.fold(f)).collect()),
+            }
+        }
+
+        let folded = match self {
+            RustType::Common(common) => RustType::Common(fold_common(common, f)),
+            RustType::ImplTrait(common) => RustType::ImplTrait(fold_common(common, f)),
+            RustType::TraitObject { kind, trait_type } => RustType::TraitObject {
+                kind: kind.clone(),
+                trait_type: fold_common(trait_type, f),
+            },
+            RustType::Tuple(types) => RustType::Tuple(types.iter().map(|t| t.fold(f)).collect()),
+            RustType::FunctionPointer(function) => {
+                RustType::FunctionPointer(RustFunctionPointerType {
+                    return_type: Box::new(function.return_type.fold(f)),
+                    arguments: function.arguments.iter().map(|t| t.fold(f)).collect(),
+                })
+            }
+            RustType::PointerLike {
+                kind,
+                is_const,
+                target,
+            } => RustType::PointerLike {
+                kind: kind.clone(),
+                is_const: *is_const,
+                target: Box::new(target.fold(f)),
+            },
+            RustType::Slice(item) => RustType::Slice(Box::new(item.fold(f))),
+            RustType::Array { item, len } => RustType::Array {
+                item: Box::new(item.fold(f)),
+                len: *len,
+            },
+            RustType::Primitive(_) | RustType::GenericParameter(_) => self.clone(),
+        };
+        f(&folded).unwrap_or(folded)
+    }
+
+    /// Returns a copy of this type with `f` applied to all paths within the type,
+    /// including generic arguments and function pointer types.
+    /// For example, this can be used to replace the crate name of all types.
+    pub fn map_paths<F: Fn(&RustPath) -> RustPath>(&self, f: &F) -> RustType {
+        self.fold(&mut |rust_type| {
+            let map_common = |common: &RustCommonType| RustCommonType {
+                path: f(&common.path),
+                generic_arguments: common.generic_arguments.clone(),
+            };
+            match rust_type {
+                RustType::Common(common) => Some(RustType::Common(map_common(common))),
+                RustType::ImplTrait(common) => Some(RustType::ImplTrait(map_common(common))),
+                RustType::TraitObject { kind, trait_type } => Some(RustType::TraitObject {
+                    kind: kind.clone(),
+                    trait_type: map_common(trait_type),
+                }),
+                _ => None,
+            }
+        })
+    }
+
+    /// Returns a copy of this type with lifetimes removed from all references.
+    pub fn without_lifetimes(&self) -> RustType {
+        self.map_lifetimes(&|_| None)
+    }
+
+    /// Returns a hash of this type that ignores lifetime names, so `&'a T` and `&'b T`
+    /// have the same hash. This is intended for cache keys where such types are
+    /// considered equal; the derived `Hash` implementation takes lifetimes into account.
+    ///
+    /// The hash is a `hash_bytes` digest of the code of the type without lifetimes,
+    /// so it doesn't depend on the platform or the compiler version and
+    /// can be persisted.
+    pub fn stable_hash(&self) -> String {
+        hash_bytes(self.without_lifetimes().to_rust_code(None).as_bytes())
+    }
+
+    /// Returns names of all lifetimes used in this type
+    /// (including generic arguments) in order of first appearance.
+    /// Each lifetime is only returned once.
+    pub fn collect_lifetimes(&self) -> Vec<String> {
+        self.reference_lifetimes()
+            .into_iter()
+            .flatten()
+            .unique()
+            .map(String::from)
+            .collect()
+    }
+
+    /// Returns a copy of this type with lifetimes renamed according to `map`.
+    /// Lifetimes not present in `map` are left unchanged.
+    pub fn replace_lifetimes(&self, map: &HashMap<String, String>) -> RustType {
+        self.map_lifetimes(&|lifetime| {
+            lifetime.map(|lifetime| {
+                map.get(lifetime)
+                    .cloned()
+                    .unwrap_or_else(|| lifetime.to_string())
+            })
+        })
+    }
+
     /// Returns name of the lifetime of this type,
     /// or `None` if there isn't any lifetime in this type.
     pub fn lifetime(&self) -> Option<&str> {
-        if let RustType::PointerLike { kind, .. } = self {
-            if let RustPointerLikeTypeKind::Reference { lifetime } = kind {
-                return lifetime.as_ref().map(String::as_str);
+        match self {
+            RustType::PointerLike {
+                kind: RustPointerLikeTypeKind::Reference { lifetime },
+                ..
             }
+            | RustType::TraitObject {
+                kind: RustTraitObjectKind::Reference { lifetime, .. },
+                ..
+            } => lifetime.as_ref().map(String::as_str),
+            _ => None,
         }
-        None
     }
     /// Returns true if indirection that is applied last has const qualifier.
     pub fn is_const_pointer_like(&self) -> Result<bool> {
@@ -673,7 +1687,14 @@ impl RustType {
             RustType::Primitive(_) | RustType::GenericParameter(_) => false,
             RustType::Common(RustCommonType {
                 generic_arguments, ..
-            }) => {
+            })
+            | RustType::TraitObject {
+                trait_type:
+                    RustCommonType {
+                        generic_arguments, ..
+                    },
+                ..
+            } => {
                 if let Some(args) = generic_arguments {
                     if args.iter().any(RustType::is_unsafe_argument) {
                         return true;
@@ -687,6 +1708,7 @@ impl RustType {
                     || function.arguments.iter().any(RustType::is_unsafe_argument)
             }
             RustType::ImplTrait(_) => true,
+            RustType::Slice(item) | RustType::Array { item, .. } => item.is_unsafe_argument(),
         }
     }
 
@@ -698,6 +1720,22 @@ impl RustType {
         }
     }
 
+    /// Removes one level of indirection from this type, e.g. converts
+    /// `*const T` or `&T` to `T` and `*mut *mut T` to `*mut T`.
+    /// `Option<&T>` and `Option<*const T>` are treated as nullable
+    /// pointer-like types and also produce `T`.
+    pub fn dereference(&self) -> Result<RustType> {
+        if let Some(arg) = self.option_argument() {
+            if let RustType::PointerLike { target, .. } = arg {
+                return Ok((**target).clone());
+            }
+        }
+        match self {
+            RustType::PointerLike { target, .. } => Ok((**target).clone()),
+            _ => bail!("can't dereference a type without indirection: {:?}", self),
+        }
+    }
+
     pub fn ptr_to_ref(&self, is_const1: bool) -> Result<Self> {
         let mut r = self.clone();
         if let RustType::PointerLike { is_const, kind, .. } = &mut r {
@@ -787,7 +1825,36 @@ impl RustType {
                     false
                 }
             }
+            RustType::TraitObject { kind, trait_type } => {
+                if let RustType::TraitObject {
+                    kind: other_kind,
+                    trait_type: other_trait_type,
+                } = other
+                {
+                    kind == other_kind && trait_type.can_be_same_as(other_trait_type)
+                } else {
+                    false
+                }
+            }
             RustType::GenericParameter(_) => self == other,
+            RustType::Slice(item) => {
+                if let RustType::Slice(other_item) = other {
+                    item.can_be_same_as(other_item)
+                } else {
+                    false
+                }
+            }
+            RustType::Array { item, len } => {
+                if let RustType::Array {
+                    item: other_item,
+                    len: other_len,
+                } = other
+                {
+                    len == other_len && item.can_be_same_as(other_item)
+                } else {
+                    false
+                }
+            }
         }
     }
 }
@@ -0,0 +1,42 @@
+use crate::cpp_data::{CppOrigin, CppOriginLocation, CppOriginResultExt};
+use itertools::Itertools;
+use ritual_common::errors::{bail, Result};
+
+fn failing_operation() -> Result<()> {
+    bail!("unsupported argument type");
+}
+
+#[test]
+fn cpp_origin_display() {
+    let location = CppOriginLocation {
+        include_file_path: "/usr/include/qt/QtCore/qstring.h".into(),
+        line: 123,
+        column: 5,
+    };
+    let origin = CppOrigin::with_location("QString::toHtml", &location);
+    assert_eq!(origin.header.as_ref().unwrap(), "qstring.h");
+    assert_eq!(origin.to_string(), "QString::toHtml (qstring.h:123)");
+    assert_eq!(CppOrigin::new("QString").to_string(), "QString");
+}
+
+#[test]
+fn error_with_cpp_origin() {
+    let location = CppOriginLocation {
+        include_file_path: "/usr/include/qt/QtCore/qstring.h".into(),
+        line: 123,
+        column: 5,
+    };
+    let origin = CppOrigin::with_location("QString::toHtml", &location);
+    let err = failing_operation().with_cpp_origin(&origin).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "while processing QString::toHtml (qstring.h:123)"
+    );
+    assert_eq!(
+        err.iter_chain().map(ToString::to_string).join(": "),
+        "while processing QString::toHtml (qstring.h:123): unsupported argument type"
+    );
+
+    let result: Result<i32> = Ok(1);
+    assert_eq!(result.with_cpp_origin(&origin).unwrap(), 1);
+}
@@ -1,4 +1,6 @@
 use crate::cpp_operator::*;
+use crate::cpp_type::CppType;
+use std::collections::HashSet;
 
 #[test]
 fn info1() {
@@ -6,4 +8,239 @@ fn info1() {
     assert_eq!(info.function_name_suffix.unwrap(), "%");
     assert_eq!(info.arguments_count, 2);
     assert_eq!(info.allows_variadic_arguments, false);
+    assert_eq!(info.member_only, false);
+}
+
+#[test]
+fn c_names_are_unique() {
+    let mut operators = CppOperator::all();
+    operators.push(CppOperator::Conversion(CppType::Void));
+
+    let mut names = HashSet::new();
+    for operator in &operators {
+        let name = operator.c_name();
+        assert!(!name.is_empty());
+        assert!(
+            name.chars().all(|c| c.is_ascii_lowercase() || c == '_'),
+            "invalid c_name: {:?}",
+            name
+        );
+        assert!(names.insert(name), "duplicate c_name: {:?}", name);
+    }
+    assert_ne!(
+        CppOperator::PrefixIncrement.c_name(),
+        CppOperator::PostfixIncrement.c_name()
+    );
+}
+
+#[test]
+fn three_way_comparison() {
+    let info = CppOperator::ThreeWayComparison.info();
+    assert_eq!(info.function_name_suffix, Some("<=>"));
+    assert_eq!(info.arguments_count, 2);
+    assert_eq!(info.allows_variadic_arguments, false);
+    assert!(CppOperator::all().contains(&CppOperator::ThreeWayComparison));
+}
+
+fn categories(operator: &CppOperator) -> Vec<&'static str> {
+    let mut r = Vec::new();
+    if operator.is_arithmetic() {
+        r.push("arithmetic");
+    }
+    if operator.is_comparison() {
+        r.push("comparison");
+    }
+    if operator.is_logical() {
+        r.push("logical");
+    }
+    if operator.is_bitwise() {
+        r.push("bitwise");
+    }
+    if operator.is_assignment() {
+        r.push("assignment");
+    }
+    if operator.is_unary() {
+        r.push("unary");
+    }
+    r
+}
+
+#[test]
+fn classification() {
+    let table = vec![
+        (CppOperator::Addition, "arithmetic"),
+        (CppOperator::UnaryMinus, "arithmetic,unary"),
+        (CppOperator::PostfixIncrement, "arithmetic,unary"),
+        (CppOperator::GreaterThan, "comparison"),
+        (CppOperator::ThreeWayComparison, "comparison"),
+        (CppOperator::LogicalAnd, "logical"),
+        (CppOperator::LogicalNot, "logical,unary"),
+        (CppOperator::BitwiseXor, "bitwise"),
+        (CppOperator::BitwiseNot, "bitwise,unary"),
+        (CppOperator::AdditionAssignment, "assignment"),
+        (CppOperator::Assignment, "assignment"),
+        (CppOperator::Indirection, "unary"),
+        (CppOperator::Subscript, ""),
+    ];
+    for (operator, expected) in table {
+        assert_eq!(categories(&operator).join(","), expected, "{:?}", operator);
+    }
+}
+
+#[test]
+fn rust_traits() {
+    let arithmetic = vec![
+        (CppOperator::Addition, "std::ops::Add", "add"),
+        (CppOperator::Subtraction, "std::ops::Sub", "sub"),
+        (CppOperator::Multiplication, "std::ops::Mul", "mul"),
+        (CppOperator::Division, "std::ops::Div", "div"),
+        (CppOperator::Modulo, "std::ops::Rem", "rem"),
+        (CppOperator::UnaryMinus, "std::ops::Neg", "neg"),
+        (
+            CppOperator::AdditionAssignment,
+            "std::ops::AddAssign",
+            "add_assign",
+        ),
+        (CppOperator::Subscript, "std::ops::Index", "index"),
+        (CppOperator::Indirection, "std::ops::Deref", "deref"),
+    ];
+    let comparison = vec![
+        (CppOperator::EqualTo, "std::cmp::PartialEq", "eq"),
+        (CppOperator::NotEqualTo, "std::cmp::PartialEq", "ne"),
+        (CppOperator::GreaterThan, "std::cmp::PartialOrd", "gt"),
+        (CppOperator::LessThan, "std::cmp::PartialOrd", "lt"),
+        (
+            CppOperator::GreaterThanOrEqualTo,
+            "std::cmp::PartialOrd",
+            "ge",
+        ),
+        (CppOperator::LessThanOrEqualTo, "std::cmp::PartialOrd", "le"),
+    ];
+    for (operator, trait_path, method) in arithmetic.into_iter().chain(comparison) {
+        assert_eq!(operator.rust_trait(), Some(trait_path), "{:?}", operator);
+        assert_eq!(operator.rust_trait_method(), Some(method), "{:?}", operator);
+    }
+
+    for operator in &[
+        CppOperator::Comma,
+        CppOperator::New,
+        CppOperator::FunctionCall,
+    ] {
+        assert_eq!(operator.rust_trait(), None);
+        assert_eq!(operator.rust_trait_method(), None);
+    }
+}
+
+#[test]
+fn rust_mapping() {
+    assert_eq!(
+        CppOperator::Addition.rust_mapping(),
+        Some(RustOperatorMapping::Trait {
+            trait_path: "std::ops::Add",
+            method: "add",
+        })
+    );
+    let increment_decrement = vec![
+        (CppOperator::PrefixIncrement, "inc"),
+        (CppOperator::PostfixIncrement, "inc_postfix"),
+        (CppOperator::PrefixDecrement, "dec"),
+        (CppOperator::PostfixDecrement, "dec_postfix"),
+    ];
+    for (operator, method) in &increment_decrement {
+        assert_eq!(
+            operator.rust_mapping(),
+            Some(RustOperatorMapping::InherentMethod(*method)),
+            "{:?}",
+            operator
+        );
+        assert_eq!(operator.rust_trait(), None);
+    }
+    let names = increment_decrement
+        .iter()
+        .map(|(_, method)| *method)
+        .collect::<HashSet<_>>();
+    assert_eq!(names.len(), 4);
+
+    assert_eq!(CppOperator::Comma.rust_mapping(), None);
+    assert_eq!(CppOperator::Conversion(CppType::Void).rust_mapping(), None);
+}
+
+#[test]
+fn from_cpp_name() {
+    assert_eq!(
+        CppOperator::from_cpp_name("operator+"),
+        Some(CppOperator::Addition)
+    );
+    assert_eq!(
+        CppOperator::from_cpp_name("operator[]"),
+        Some(CppOperator::Subscript)
+    );
+    assert_eq!(
+        CppOperator::from_cpp_name("operator new[]"),
+        Some(CppOperator::NewArray)
+    );
+    assert_eq!(
+        CppOperator::from_cpp_name("operator new"),
+        Some(CppOperator::New)
+    );
+    assert_eq!(
+        CppOperator::from_cpp_name("operator delete"),
+        Some(CppOperator::Delete)
+    );
+    assert_eq!(
+        CppOperator::from_cpp_name("operator<=>"),
+        Some(CppOperator::ThreeWayComparison)
+    );
+    assert_eq!(CppOperator::from_cpp_name("operator int"), None);
+    assert_eq!(CppOperator::from_cpp_name("operator"), None);
+    assert_eq!(CppOperator::from_cpp_name("operators"), None);
+    assert_eq!(CppOperator::from_cpp_name("operator+++"), None);
+    assert_eq!(CppOperator::from_cpp_name("add"), None);
+}
+
+#[test]
+fn precedence() {
+    assert!(CppOperator::Multiplication.precedence() > CppOperator::Addition.precedence());
+    assert!(CppOperator::Addition.precedence() > CppOperator::BitwiseLeftShift.precedence());
+    assert!(CppOperator::LessThan.precedence() > CppOperator::EqualTo.precedence());
+    assert!(CppOperator::LogicalAnd.precedence() > CppOperator::LogicalOr.precedence());
+    assert!(CppOperator::Assignment.precedence() > CppOperator::Comma.precedence());
+    for operator in CppOperator::all() {
+        assert!(CppOperator::Subscript.precedence() >= operator.precedence());
+        assert!(CppOperator::FunctionCall.precedence() >= operator.precedence());
+    }
+}
+
+#[test]
+fn associativity() {
+    assert_eq!(
+        CppOperator::Assignment.associativity(),
+        Associativity::Right
+    );
+    assert_eq!(
+        CppOperator::AdditionAssignment.associativity(),
+        Associativity::Right
+    );
+    assert_eq!(
+        CppOperator::LogicalNot.associativity(),
+        Associativity::Right
+    );
+    assert_eq!(CppOperator::Addition.associativity(), Associativity::Left);
+    assert_eq!(
+        CppOperator::PostfixIncrement.associativity(),
+        Associativity::Left
+    );
+    assert_eq!(CppOperator::Comma.associativity(), Associativity::Left);
+}
+
+#[test]
+fn member_only() {
+    assert!(CppOperator::Subscript.info().member_only);
+    assert!(CppOperator::Assignment.info().member_only);
+    assert!(CppOperator::StructureDereference.info().member_only);
+    assert!(CppOperator::FunctionCall.info().member_only);
+    assert!(CppOperator::Conversion(CppType::Void).info().member_only);
+    assert!(!CppOperator::Addition.info().member_only);
+    assert!(!CppOperator::AdditionAssignment.info().member_only);
+    assert!(!CppOperator::EqualTo.info().member_only);
 }
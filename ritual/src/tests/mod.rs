@@ -1,7 +1,10 @@
 #![allow(clippy::cognitive_complexity)]
 
+mod cpp_data;
 mod cpp_ffi_data;
 mod cpp_method;
 mod cpp_operator;
 mod cpp_parser;
 mod cpp_type;
+mod rust_info;
+mod rust_type;
@@ -0,0 +1,854 @@
+use crate::rust_info::{
+    conversion_to_code, default_arguments_overloads, disambiguate_overloads, drop_impl_to_code,
+    extern_block_to_code, flags_wrapper_to_code, function_signature_to_code,
+    function_signature_with_bounds_to_code, link_attribute_to_code, linked_extern_block_to_code,
+    method_signature_to_code, partial_eq_impl_to_code, partial_ord_impl_to_code,
+    pointer_wrapper_to_code, scoped_function_signature_to_code, RustDefaultedArgument, RustEnum,
+    RustFfiModule, RustFunction, RustFunctionArgument, RustFunctionKind, RustFunctionScope,
+    RustFunctionSelfArgKind, RustLinkKind,
+};
+use crate::rust_type::{
+    RustCommonType, RustFinalType, RustPath, RustToFfiTypeConversion, RustType,
+};
+use ritual_common::utils::{dylib_file_name, staticlib_suffix};
+use std::collections::BTreeMap;
+
+fn common(path: &str) -> RustType {
+    RustType::Common(RustCommonType {
+        path: RustPath::from_good_str(path),
+        generic_arguments: None,
+    })
+}
+
+fn final_type(rust_type: RustType) -> RustFinalType {
+    RustFinalType::new(rust_type, RustToFfiTypeConversion::None).unwrap()
+}
+
+fn arg(name: &str, argument_type: RustType) -> RustFunctionArgument {
+    RustFunctionArgument {
+        name: name.into(),
+        argument_type: final_type(argument_type),
+        ffi_index: 0,
+    }
+}
+
+fn ffi_function(
+    path: &str,
+    arguments: Vec<RustFunctionArgument>,
+    return_type: RustType,
+) -> RustFunction {
+    let arguments = arguments
+        .into_iter()
+        .enumerate()
+        .map(|(index, arg)| RustFunctionArgument {
+            ffi_index: index,
+            ..arg
+        })
+        .collect();
+    RustFunction {
+        is_public: true,
+        is_unsafe: false,
+        path: RustPath::from_good_str(path),
+        kind: RustFunctionKind::FfiFunction,
+        arguments,
+        return_type: final_type(return_type),
+    }
+}
+
+#[test]
+fn signature_single_reference_elides_lifetime() {
+    let arg_type = RustType::new_reference(true, common("foo::Foo")).with_lifetime("a".into());
+    let return_type = RustType::new_reference(true, common("foo::Bar")).with_lifetime("a".into());
+    let code = function_signature_to_code(
+        "get",
+        &[
+            arg("foo", arg_type),
+            arg("i", RustType::Primitive("i32".into())),
+        ],
+        &return_type,
+        None,
+    );
+    assert_eq!(code, "fn get(foo: &::foo::Foo, i: i32) -> &::foo::Bar");
+}
+
+#[test]
+fn signature_two_references_keep_lifetimes() {
+    let arg1 = RustType::new_reference(true, common("foo::Foo")).with_lifetime("a".into());
+    let arg2 = RustType::new_reference(false, common("foo::Foo")).with_lifetime("b".into());
+    let return_type = RustType::new_reference(true, common("foo::Bar")).with_lifetime("a".into());
+    let code = function_signature_to_code(
+        "get",
+        &[arg("foo1", arg1), arg("foo2", arg2)],
+        &return_type,
+        Some("foo"),
+    );
+    assert_eq!(
+        code,
+        "fn get<'a, 'b>(foo1: &'a crate::Foo, foo2: &'b mut crate::Foo) -> &'a crate::Bar"
+    );
+}
+
+#[test]
+fn signature_static_lifetime() {
+    let return_type =
+        RustType::new_reference(true, common("foo::Bar")).with_lifetime("static".into());
+    let code = function_signature_to_code("get", &[], &return_type, None);
+    assert_eq!(code, "fn get() -> &'static ::foo::Bar");
+}
+
+#[test]
+fn ffi_function_void_with_arguments() {
+    let function = ffi_function(
+        "foo::ffi::foo_set",
+        vec![
+            arg("arg0", RustType::new_pointer(false, common("foo::Foo"))),
+            arg("arg1", RustType::Primitive("i32".into())),
+        ],
+        RustType::unit(),
+    );
+    assert_eq!(
+        function.ffi_declaration_to_code(Some("foo")).unwrap(),
+        "pub fn foo_set(arg0: *mut crate::Foo, arg1: i32);"
+    );
+}
+
+#[test]
+fn ffi_function_value_without_arguments() {
+    let function = ffi_function(
+        "foo::ffi::foo_new",
+        Vec::new(),
+        RustType::new_pointer(false, common("foo::Bar")),
+    );
+    assert_eq!(
+        function.ffi_declaration_to_code(None).unwrap(),
+        "pub fn foo_new() -> *mut ::foo::Bar;"
+    );
+}
+
+#[test]
+fn ffi_extern_block() {
+    let function =
+        |name: &str| ffi_function(&format!("foo::ffi::{}", name), Vec::new(), RustType::unit());
+    let functions = vec![function("foo_b"), function("foo_a")];
+    assert_eq!(
+        extern_block_to_code(&functions, Some("foo"), None).unwrap(),
+        "#[link(name = \"foo\")]\n\
+         extern \"C\" {\n    \
+         pub fn foo_a();\n    \
+         pub fn foo_b();\n\
+         }\n"
+    );
+    assert_eq!(
+        extern_block_to_code(&functions[..1], None, None).unwrap(),
+        "extern \"C\" {\n    pub fn foo_b();\n}\n"
+    );
+}
+
+#[test]
+fn link_attributes() {
+    assert_eq!(
+        link_attribute_to_code("foo", RustLinkKind::Dylib),
+        "#[link(name = \"foo\", kind = \"dylib\")]"
+    );
+    assert_eq!(
+        link_attribute_to_code("foo", RustLinkKind::Static),
+        "#[link(name = \"foo\", kind = \"static\")]"
+    );
+    assert_eq!(
+        link_attribute_to_code(&dylib_file_name("foo"), RustLinkKind::Dylib),
+        "#[link(name = \"foo\", kind = \"dylib\")]"
+    );
+    assert_eq!(
+        link_attribute_to_code(&format!("foo{}", staticlib_suffix()), RustLinkKind::Static),
+        "#[link(name = \"foo\", kind = \"static\")]"
+    );
+
+    let functions = vec![ffi_function(
+        "foo::ffi::foo_a",
+        Vec::new(),
+        RustType::unit(),
+    )];
+    assert_eq!(
+        linked_extern_block_to_code(&functions, "foo", RustLinkKind::Static, None).unwrap(),
+        "#[link(name = \"foo\", kind = \"static\")]\n\
+         extern \"C\" {\n    \
+         pub fn foo_a();\n\
+         }\n"
+    );
+}
+
+#[test]
+fn ffi_function_signature_key() {
+    let function1 = ffi_function(
+        "foo::ffi::foo_get",
+        vec![
+            arg(
+                "this_ptr",
+                RustType::new_reference(true, common("foo::Foo")).with_lifetime("a".into()),
+            ),
+            arg("i", RustType::Primitive("i32".into())),
+        ],
+        RustType::new_pointer(true, common("foo::Bar")),
+    );
+    let mut function2 = function1.clone();
+    function2.arguments[0].name = "other".into();
+    function2.arguments[0].argument_type =
+        final_type(RustType::new_reference(true, common("foo::Foo")).with_lifetime("b".into()));
+    function2.arguments[1].name = "index".into();
+    assert_eq!(function1.signature_key(), function2.signature_key());
+    assert_eq!(
+        function1.signature_key(),
+        "foo_get(&::foo::Foo, i32) -> *const ::foo::Bar"
+    );
+
+    let mut function3 = function1.clone();
+    function3.arguments[1].argument_type = final_type(RustType::Primitive("u32".into()));
+    assert_ne!(function1.signature_key(), function3.signature_key());
+}
+
+#[test]
+fn ffi_function_deduplicate_argument_names() {
+    let int = || RustType::Primitive("i32".into());
+    let mut function = ffi_function(
+        "foo::ffi::foo",
+        vec![
+            arg("", int()),
+            arg("type", int()),
+            arg("", int()),
+            arg("arg2", int()),
+            arg("x", int()),
+            arg("x", int()),
+        ],
+        RustType::unit(),
+    );
+    function.deduplicate_argument_names();
+    let names = function
+        .arguments
+        .iter()
+        .map(|arg| arg.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec!["arg0", "type_", "arg2_", "arg2", "arg4", "arg5"]
+    );
+}
+
+#[test]
+fn disambiguate_overloaded_functions() {
+    let path = |s: &str| RustPath::from_good_str(s);
+    let functions = vec![
+        (path("qt_core::QFoo::set"), vec![]),
+        (
+            path("qt_core::QFoo::set"),
+            vec![RustType::Primitive("i32".into())],
+        ),
+        (path("qt_core::QFoo::set"), vec![common("qt_core::QString")]),
+        (
+            path("qt_core::QFoo::get"),
+            vec![RustType::Primitive("i32".into())],
+        ),
+    ];
+    let expected = vec![
+        path("qt_core::QFoo::set"),
+        path("qt_core::QFoo::set_i32"),
+        path("qt_core::QFoo::set_q_string"),
+        path("qt_core::QFoo::get"),
+    ];
+    assert_eq!(disambiguate_overloads(&functions).unwrap(), expected);
+    assert_eq!(disambiguate_overloads(&functions).unwrap(), expected);
+}
+
+#[test]
+fn disambiguate_overloads_with_same_captions() {
+    let path = RustPath::from_good_str("foo::at");
+    let functions = vec![
+        (path.clone(), vec![common("foo::a::Item")]),
+        (path.clone(), vec![common("foo::b::Item")]),
+    ];
+    assert_eq!(
+        disambiguate_overloads(&functions).unwrap(),
+        vec![
+            RustPath::from_good_str("foo::at_item_0"),
+            RustPath::from_good_str("foo::at_item_1"),
+        ]
+    );
+}
+
+#[test]
+fn disambiguate_overloads_with_existing_names() {
+    let path = |s: &str| RustPath::from_good_str(s);
+    let int = || RustType::Primitive("i32".into());
+    let functions = vec![
+        (path("foo::set"), vec![int()]),
+        (path("foo::set"), vec![RustType::Primitive("bool".into())]),
+        (path("foo::set_i32"), vec![int()]),
+        (path("foo::get"), vec![]),
+        (path("foo::get"), vec![]),
+        (path("foo::get_0"), vec![]),
+    ];
+    assert_eq!(
+        disambiguate_overloads(&functions).unwrap(),
+        vec![
+            path("foo::set_i32_0"),
+            path("foo::set_bool"),
+            path("foo::set_i32"),
+            path("foo::get_1"),
+            path("foo::get_2"),
+            path("foo::get_0"),
+        ]
+    );
+}
+
+#[test]
+fn scoped_signature() {
+    let function = ffi_function(
+        "qt_core::ffi::size",
+        vec![
+            arg(
+                "this_ptr",
+                RustType::new_pointer(true, common("qt_core::QString")),
+            ),
+            arg("i", RustType::Primitive("i32".into())),
+        ],
+        RustType::Primitive("i32".into()),
+    );
+    let signature = |scope: &RustFunctionScope, self_arg_kind| {
+        scoped_function_signature_to_code(
+            function.path.last(),
+            &function.arguments,
+            function.return_type.api_type(),
+            scope,
+            self_arg_kind,
+            Some("qt_core"),
+        )
+    };
+    let impl_scope = RustFunctionScope::Impl {
+        target_type: common("qt_core::QString"),
+    };
+
+    assert_eq!(
+        signature(&RustFunctionScope::Free, RustFunctionSelfArgKind::None).unwrap(),
+        "pub fn size(this_ptr: *const crate::QString, i: i32) -> i32"
+    );
+    assert_eq!(
+        signature(&impl_scope, RustFunctionSelfArgKind::ConstRef).unwrap(),
+        "    pub fn size(&self, i: i32) -> i32"
+    );
+    assert_eq!(
+        signature(&impl_scope, RustFunctionSelfArgKind::Value).unwrap(),
+        "    pub fn size(self, i: i32) -> i32"
+    );
+    assert_eq!(
+        signature(
+            &RustFunctionScope::TraitImpl,
+            RustFunctionSelfArgKind::ConstRef
+        )
+        .unwrap(),
+        "    fn size(&self, i: i32) -> i32"
+    );
+    assert!(signature(&impl_scope, RustFunctionSelfArgKind::MutRef).is_err());
+    assert!(signature(&RustFunctionScope::Free, RustFunctionSelfArgKind::ConstRef).is_err());
+
+    let code = scoped_function_signature_to_code(
+        "clear",
+        &function.arguments[..1],
+        &RustType::unit(),
+        &impl_scope,
+        RustFunctionSelfArgKind::ConstRef,
+        None,
+    )
+    .unwrap();
+    assert_eq!(code, "    pub fn clear(&self)");
+}
+
+#[test]
+fn scoped_signature_returning_reference() {
+    let this_arg = arg(
+        "this_ptr",
+        RustType::new_pointer(true, common("qt_core::QString")),
+    );
+    let reference = |lifetime: &str, target: &str| {
+        RustType::new_reference(true, common(target)).with_lifetime(lifetime.into())
+    };
+    let impl_scope = RustFunctionScope::Impl {
+        target_type: common("qt_core::QString"),
+    };
+    let signature = |arguments: &[RustFunctionArgument], return_type: &RustType| {
+        scoped_function_signature_to_code(
+            "get",
+            arguments,
+            return_type,
+            &impl_scope,
+            RustFunctionSelfArgKind::ConstRef,
+            None,
+        )
+    };
+
+    // the returned reference can only borrow from `self`
+    let code = signature(&[this_arg.clone()], &reference("a", "qt_core::QChar")).unwrap();
+    assert_eq!(code, "    pub fn get(&self) -> &::qt_core::QChar");
+
+    // the returned reference borrows from `x`, not from `self`
+    let code = signature(
+        &[this_arg.clone(), arg("x", reference("a", "qt_core::QChar"))],
+        &reference("a", "qt_core::QChar"),
+    )
+    .unwrap();
+    assert_eq!(
+        code,
+        "    pub fn get<'a>(&self, x: &'a ::qt_core::QChar) -> &'a ::qt_core::QChar"
+    );
+
+    let code = signature(
+        &[this_arg.clone(), arg("x", reference("b", "qt_core::QChar"))],
+        &reference("a", "qt_core::QChar"),
+    )
+    .unwrap();
+    assert_eq!(
+        code,
+        "    pub fn get(&self, x: &::qt_core::QChar) -> &::qt_core::QChar"
+    );
+
+    let code = signature(
+        &[
+            this_arg.clone(),
+            arg("x", reference("b", "qt_core::QChar")),
+            arg("y", reference("b", "qt_core::QChar")),
+        ],
+        &reference("a", "qt_core::QChar"),
+    )
+    .unwrap();
+    assert_eq!(
+        code,
+        "    pub fn get<'b, 'a>(&'a self, x: &'b ::qt_core::QChar, y: &'b ::qt_core::QChar) \
+         -> &'a ::qt_core::QChar"
+    );
+
+    let pair = RustType::Tuple(vec![
+        reference("a", "qt_core::QChar"),
+        reference("b", "qt_core::QChar"),
+    ]);
+    assert!(signature(&[this_arg], &pair).is_err());
+}
+
+#[test]
+fn const_and_mut_method_signatures() {
+    let scope = RustFunctionScope::Impl {
+        target_type: common("qt_core::QString"),
+    };
+    let method = |this_is_const: bool, is_const_method: bool| {
+        method_signature_to_code(
+            if is_const_method { "size" } else { "clear" },
+            &[arg(
+                "this_ptr",
+                RustType::new_pointer(this_is_const, common("qt_core::QString")),
+            )],
+            &RustType::unit(),
+            &scope,
+            is_const_method,
+            None,
+        )
+    };
+    assert_eq!(method(true, true).unwrap(), "    pub fn size(&self)");
+    assert_eq!(method(false, false).unwrap(), "    pub fn clear(&mut self)");
+    // receiver must match constness of the `this` pointer
+    assert!(method(false, true).is_err());
+    assert!(method(true, false).is_err());
+
+    let this_arg = arg(
+        "this_ptr",
+        RustType::new_pointer(true, common("qt_core::QString")),
+    );
+    let return_type =
+        RustType::new_reference(true, common("qt_core::QChar")).with_lifetime("a".into());
+    let code =
+        method_signature_to_code("at", &[this_arg], &return_type, &scope, true, None).unwrap();
+    assert_eq!(code, "    pub fn at(&self) -> &::qt_core::QChar");
+
+    assert_eq!(
+        RustFunctionSelfArgKind::from_const_method(true),
+        RustFunctionSelfArgKind::ConstRef
+    );
+    assert_eq!(
+        RustFunctionSelfArgKind::from_const_method(false),
+        RustFunctionSelfArgKind::MutRef
+    );
+}
+
+#[test]
+fn conversion_impl() {
+    let source = RustPath::from_good_str("qt_core::QString");
+    let target = RustType::Primitive("i32".into());
+    let function = ffi_function(
+        "qt_core::ffi::ctr_qt_core_ffi_QString_operator_int",
+        vec![arg(
+            "this_ptr",
+            RustType::new_pointer(true, common("qt_core::QString")),
+        )],
+        target.clone(),
+    );
+    let code = conversion_to_code(&source, &target, &function, false, None).unwrap();
+    assert_eq!(
+        code,
+        "impl<'a> From<&'a ::qt_core::QString> for i32 {\n    \
+         fn from(value: &'a ::qt_core::QString) -> Self {\n        \
+         unsafe { ::qt_core::ffi::ctr_qt_core_ffi_QString_operator_int(value.as_ptr()) }\n    \
+         }\n}\n"
+    );
+
+    let code = conversion_to_code(&source, &target, &function, true, Some("qt_core")).unwrap();
+    assert_eq!(
+        code,
+        "impl crate::QString {\n    pub fn to_i32(&self) -> i32 {\n        \
+         unsafe { crate::ffi::ctr_qt_core_ffi_QString_operator_int(self.as_ptr()) }\n    \
+         }\n}\n"
+    );
+
+    let other_target = RustType::Primitive("bool".into());
+    assert!(conversion_to_code(&source, &other_target, &function, false, None).is_err());
+    let mut bad_function = function.clone();
+    bad_function.arguments[0].argument_type =
+        final_type(RustType::new_pointer(false, common("qt_core::QString")));
+    assert!(conversion_to_code(&source, &target, &bad_function, false, None).is_err());
+}
+
+#[test]
+fn partial_eq_impl() {
+    let this_type = RustType::new_pointer(true, common("qt_core::QString"));
+    let eq_function = ffi_function(
+        "qt_core::ffi::ctr_qt_core_ffi_QString_operator_eq",
+        vec![arg("this_ptr", this_type.clone()), arg("other", this_type)],
+        RustType::Primitive("bool".into()),
+    );
+    let path = RustPath::from_good_str("qt_core::QString");
+    let code = partial_eq_impl_to_code(&path, &eq_function, None, Some("qt_core")).unwrap();
+    assert_eq!(
+        code,
+        "impl PartialEq for crate::QString {\n    fn eq(&self, other: &Self) -> bool {\n        \
+         unsafe { crate::ffi::ctr_qt_core_ffi_QString_operator_eq(self.as_ptr(), other.as_ptr()) }\
+         \n    }\n}\n"
+    );
+
+    let mut ne_function = eq_function.clone();
+    ne_function.path = RustPath::from_good_str("qt_core::ffi::ctr_qt_core_ffi_QString_operator_ne");
+    let code = partial_eq_impl_to_code(&path, &eq_function, Some(&ne_function), None).unwrap();
+    assert!(code.starts_with("impl PartialEq for ::qt_core::QString {\n    // "));
+    assert!(code.contains("-> bool"));
+    assert!(code.contains("unsafe { ::qt_core::ffi::ctr_qt_core_ffi_QString_operator_eq("));
+    assert!(!code.contains("ctr_qt_core_ffi_QString_operator_ne("));
+
+    let mut bad_function = eq_function.clone();
+    bad_function.return_type = final_type(RustType::Primitive("i32".into()));
+    assert!(partial_eq_impl_to_code(&path, &bad_function, None, None).is_err());
+    let mut bad_function = eq_function;
+    bad_function.arguments.pop();
+    assert!(partial_eq_impl_to_code(&path, &bad_function, None, None).is_err());
+}
+
+fn comparison_function(name: &str, return_type: RustType) -> RustFunction {
+    let this_type = RustType::new_pointer(true, common("qt_core::QString"));
+    ffi_function(
+        &format!("qt_core::ffi::{}", name),
+        vec![arg("this_ptr", this_type.clone()), arg("other", this_type)],
+        return_type,
+    )
+}
+
+#[test]
+fn ord_impl_from_three_way_comparison() {
+    let path = RustPath::from_good_str("qt_core::QString");
+    let three_way = comparison_function("ffi_cmp", RustType::Primitive("i32".into()));
+    let code = partial_ord_impl_to_code(&path, Some(&three_way), None, None, true, Some("qt_core"))
+        .unwrap();
+    assert_eq!(
+        code,
+        "impl PartialOrd for crate::QString {\n    \
+         fn partial_cmp(&self, other: &Self) -> Option<::std::cmp::Ordering> {\n        \
+         Some(self.cmp(other))\n    }\n}\n\n\
+         impl Ord for crate::QString {\n    \
+         fn cmp(&self, other: &Self) -> ::std::cmp::Ordering {\n        \
+         let result = unsafe { crate::ffi::ffi_cmp(self.as_ptr(), other.as_ptr()) };\
+         \n        \
+         result.cmp(&0)\n    }\n}\n"
+    );
+
+    let code = partial_ord_impl_to_code(&path, Some(&three_way), None, None, false, None).unwrap();
+    assert!(!code.contains("impl Ord"));
+    assert!(code.contains("::qt_core::ffi::ffi_cmp(self.as_ptr(), other.as_ptr())"));
+    assert!(code.contains("Some(ordering)"));
+
+    let bad_function = comparison_function("ffi_cmp", RustType::Primitive("bool".into()));
+    assert!(partial_ord_impl_to_code(&path, Some(&bad_function), None, None, true, None).is_err());
+}
+
+#[test]
+fn ord_impl_from_less_than() {
+    let path = RustPath::from_good_str("qt_core::QString");
+    let bool_type = RustType::Primitive("bool".into());
+    let less_than = comparison_function("ffi_lt", bool_type.clone());
+    let eq = comparison_function("ffi_eq", bool_type);
+    let code =
+        partial_ord_impl_to_code(&path, None, Some(&less_than), Some(&eq), true, None).unwrap();
+    assert!(code.contains("impl Ord for ::qt_core::QString {"));
+    assert!(code.contains("if ::qt_core::ffi::ffi_lt(self.as_ptr(), other.as_ptr()) {"));
+    assert!(code.contains("} else if ::qt_core::ffi::ffi_eq(self.as_ptr(), other.as_ptr()) {"));
+    assert!(code.contains("::std::cmp::Ordering::Greater"));
+
+    let code =
+        partial_ord_impl_to_code(&path, None, Some(&less_than), Some(&eq), false, None).unwrap();
+    assert!(!code.contains("impl Ord"));
+    assert!(code.contains("} else if !::qt_core::ffi::ffi_lt(other.as_ptr(), self.as_ptr()) {"));
+    assert!(code.contains("return None;"));
+
+    assert!(partial_ord_impl_to_code(&path, None, Some(&less_than), None, true, None).is_err());
+}
+
+#[test]
+fn drop_impl() {
+    let destructor = ffi_function(
+        "qt_core::ffi::ctr_qt_core_ffi_QString_delete",
+        vec![arg(
+            "this_ptr",
+            RustType::new_pointer(false, common("qt_core::QString")),
+        )],
+        RustType::unit(),
+    );
+    let code = drop_impl_to_code(
+        &RustPath::from_good_str("qt_core::QString"),
+        &destructor,
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        code,
+        "impl Drop for ::qt_core::QString {\n    fn drop(&mut self) {\n        \
+         unsafe { ::qt_core::ffi::ctr_qt_core_ffi_QString_delete(self.as_ptr()) }\n    }\n}\n"
+    );
+    let code = drop_impl_to_code(
+        &RustPath::from_good_str("qt_core::QString"),
+        &destructor,
+        Some("qt_core"),
+    )
+    .unwrap();
+    assert!(code.starts_with("impl Drop for crate::QString {"));
+    assert!(code.contains("unsafe"));
+    assert!(code.contains("crate::ffi::ctr_qt_core_ffi_QString_delete("));
+
+    let mut bad_destructor = destructor.clone();
+    bad_destructor.arguments.clear();
+    assert!(drop_impl_to_code(
+        &RustPath::from_good_str("qt_core::QString"),
+        &bad_destructor,
+        None
+    )
+    .is_err());
+}
+
+#[test]
+fn enum_with_explicit_discriminants() {
+    let rust_enum = RustEnum {
+        path: RustPath::from_good_str("foo::Color"),
+        variants: vec![
+            ("Red".to_string(), 0),
+            ("Green".to_string(), 1),
+            ("Blue".to_string(), 10),
+            ("Invalid".to_string(), -1),
+        ],
+        underlying_type: common("std::os::raw::c_int"),
+    };
+    assert_eq!(
+        rust_enum.to_rust_code().unwrap(),
+        "#[derive(Debug, Clone, Copy, PartialEq, Eq)]\n\
+         #[repr(i32)]\n\
+         pub enum Color {\n    \
+         Red = 0,\n    \
+         Green = 1,\n    \
+         Blue = 10,\n    \
+         Invalid = -1,\n\
+         }\n"
+    );
+
+    let mut rust_enum2 = rust_enum.clone();
+    rust_enum2.underlying_type = RustType::Primitive("u8".into());
+    assert!(rust_enum2.to_rust_code().unwrap().contains("#[repr(u8)]"));
+
+    let mut rust_enum3 = rust_enum;
+    rust_enum3.variants.push(("Other".to_string(), 10));
+    assert!(rust_enum3.to_rust_code().is_err());
+}
+
+#[test]
+fn flags_wrapper() {
+    let code = flags_wrapper_to_code(
+        &RustPath::from_good_str("qt_core::Alignment"),
+        &[("AlignLeft".to_string(), 1), ("AlignRight".to_string(), 2)],
+    )
+    .unwrap();
+    assert!(code.contains("#[repr(transparent)]\npub struct Alignment(::std::os::raw::c_uint);"));
+    assert!(code.contains("pub const AlignLeft: Alignment = Alignment(1);"));
+    assert!(code.contains("pub const AlignRight: Alignment = Alignment(2);"));
+    assert!(code.contains(
+        "impl ::std::ops::BitOr for Alignment {\n    type Output = Alignment;\n\n    \
+         fn bitor(self, other: Alignment) -> Alignment {\n        \
+         Alignment(self.0 | other.0)\n    }\n}\n"
+    ));
+    assert!(code.contains("impl ::std::ops::BitAnd for Alignment {"));
+    assert!(code.contains("Alignment(self.0 ^ other.0)"));
+    assert!(code.contains("fn not(self) -> Alignment {\n        Alignment(!self.0)\n    }"));
+    assert!(code.contains("impl From<::std::os::raw::c_uint> for Alignment {"));
+    assert!(code.contains("impl From<Alignment> for ::std::os::raw::c_uint {"));
+    assert!(
+        code.contains("pub fn to_int(&self) -> ::std::os::raw::c_uint {\n        self.0\n    }")
+    );
+
+    assert!(flags_wrapper_to_code(
+        &RustPath::from_good_str("qt_core::Alignment"),
+        &[("Invalid".to_string(), -1)],
+    )
+    .is_err());
+}
+
+#[test]
+fn ffi_module_tree() {
+    let function =
+        |name: &str| ffi_function(&format!("foo::ffi::{}", name), Vec::new(), RustType::unit());
+    let module = RustFfiModule {
+        path: RustPath::from_good_str("foo::ffi"),
+        ffi_functions: vec![function("foo_b"), function("foo_a")],
+        submodules: vec![
+            RustFfiModule {
+                path: RustPath::from_good_str("foo::ffi::second"),
+                ffi_functions: vec![function("second_a")],
+                submodules: Vec::new(),
+            },
+            RustFfiModule {
+                path: RustPath::from_good_str("foo::ffi::first"),
+                ffi_functions: Vec::new(),
+                submodules: Vec::new(),
+            },
+        ],
+    };
+    assert_eq!(
+        module.to_rust_code(Some("foo")).unwrap(),
+        "pub mod ffi {\n    \
+         extern \"C\" {\n        \
+         pub fn foo_a();\n        \
+         pub fn foo_b();\n    \
+         }\n\n    \
+         pub mod first {\n    \
+         }\n\n    \
+         pub mod second {\n        \
+         extern \"C\" {\n            \
+         pub fn second_a();\n        \
+         }\n    \
+         }\n\
+         }\n"
+    );
+}
+
+#[test]
+fn signature_with_where_clause() {
+    let into_qstring = RustType::Common(RustCommonType {
+        path: RustPath::from_good_str("std::convert::Into"),
+        generic_arguments: Some(vec![common("qt_core::QString")]),
+    });
+    let mut bounds = BTreeMap::new();
+    bounds.insert(
+        "T".to_string(),
+        vec![into_qstring, common("std::clone::Clone")],
+    );
+    bounds.insert("U".to_string(), Vec::new());
+    let code = function_signature_with_bounds_to_code(
+        "set",
+        &[
+            arg("t", RustType::GenericParameter("T".into())),
+            arg("u", RustType::GenericParameter("U".into())),
+        ],
+        &RustType::unit(),
+        &bounds,
+        None,
+    );
+    assert_eq!(
+        code,
+        "fn set<T, U>(t: T, u: U) where T: ::std::convert::Into<::qt_core::QString> \
+         + ::std::clone::Clone"
+    );
+    assert!(!code.contains("U:"));
+
+    let code = function_signature_with_bounds_to_code(
+        "set",
+        &[arg("t", RustType::GenericParameter("T".into()))],
+        &RustType::unit(),
+        &BTreeMap::new(),
+        None,
+    );
+    assert_eq!(code, "fn set<T>(t: T)");
+}
+
+#[test]
+fn default_arguments() {
+    let i32_type = RustType::Primitive("i32".into());
+    let bool_type = RustType::Primitive("bool".into());
+    let defaulted =
+        |name: &str, argument_type: &RustType, default_value: Option<&str>| RustDefaultedArgument {
+            argument: arg(name, argument_type.clone()),
+            default_value: default_value.map(|value| value.to_string()),
+        };
+    let arguments = vec![
+        defaulted("x", &i32_type, None),
+        defaulted("y", &i32_type, Some("0")),
+        defaulted("flag", &bool_type, Some("true")),
+    ];
+    let overloads = default_arguments_overloads(&arguments).unwrap();
+    assert_eq!(overloads.len(), 3);
+    assert_eq!(
+        overloads[0].arguments,
+        vec![
+            arg("x", i32_type.clone()),
+            arg("y", i32_type.clone()),
+            arg("flag", bool_type),
+        ]
+    );
+    assert!(overloads[0].default_values.is_empty());
+    assert_eq!(
+        overloads[1].arguments,
+        vec![arg("x", i32_type.clone()), arg("y", i32_type.clone())]
+    );
+    assert_eq!(overloads[1].default_values, vec!["true".to_string()]);
+    assert_eq!(overloads[2].arguments, vec![arg("x", i32_type.clone())]);
+    assert_eq!(
+        overloads[2].default_values,
+        vec!["0".to_string(), "true".to_string()]
+    );
+
+    let overloads = default_arguments_overloads(&arguments[..1]).unwrap();
+    assert_eq!(overloads.len(), 1);
+
+    let invalid = vec![
+        defaulted("y", &i32_type, Some("0")),
+        defaulted("x", &i32_type, None),
+    ];
+    assert!(default_arguments_overloads(&invalid).is_err());
+}
+
+#[test]
+fn pointer_wrapper() {
+    let path = RustPath::from_good_str("qt_core::QString");
+    let pointer_type = RustType::new_pointer(false, common("qt_core::ffi::QString"));
+    let code = pointer_wrapper_to_code(&path, &pointer_type, Some("qt_core")).unwrap();
+    assert_eq!(
+        code,
+        "#[repr(transparent)]\n\
+         pub struct QString(*mut crate::ffi::QString);\n\n\
+         impl QString {\n    \
+         #[allow(dead_code)]\n    \
+         fn from_ptr(ptr: *mut crate::ffi::QString) -> Self {\n        \
+         QString(ptr)\n    }\n\n    \
+         #[allow(dead_code)]\n    \
+         fn as_ptr(&self) -> *mut crate::ffi::QString {\n        \
+         self.0\n    }\n}\n"
+    );
+
+    let reference_type = RustType::new_reference(false, common("qt_core::ffi::QString"));
+    assert!(pointer_wrapper_to_code(&path, &reference_type, None).is_err());
+}
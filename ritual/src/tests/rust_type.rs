@@ -0,0 +1,971 @@
+use crate::cpp_data::CppPath;
+use crate::cpp_type::CppType;
+use crate::rust_info::RustTypeCaptionStrategy;
+use crate::rust_type::{
+    can_elide_lifetimes, case_insensitive_clashes, escape_rust_identifier,
+    sanitize_rust_identifier, RustCommonType, RustFinalType, RustPath, RustToFfiTypeConversion,
+    RustTraitObjectKind, RustType,
+};
+use ritual_common::utils::hash_bytes;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::os::raw::c_int;
+use std::str::FromStr;
+
+fn common(path: &str, generic_arguments: Option<Vec<RustType>>) -> RustType {
+    RustType::Common(RustCommonType {
+        path: RustPath::from_good_str(path),
+        generic_arguments,
+    })
+}
+
+#[test]
+fn to_rust_code_const_pointer() {
+    let type1 = RustType::new_pointer(true, common("qt_core::QString", None));
+    assert_eq!(type1.to_rust_code(None), "*const ::qt_core::QString");
+    assert_eq!(type1.to_rust_code(Some("qt_core")), "*const crate::QString");
+    assert_eq!(
+        type1.to_rust_code(Some("qt_gui")),
+        "*const ::qt_core::QString"
+    );
+}
+
+#[test]
+fn to_rust_code_mut_ref_with_lifetime() {
+    let type1 = RustType::new_reference(false, common("foo::Foo", None)).with_lifetime("a".into());
+    assert_eq!(type1.to_rust_code(None), "&'a mut ::foo::Foo");
+}
+
+#[test]
+fn to_rust_code_option_generic() {
+    let vec = common(
+        "std::vec::Vec",
+        Some(vec![RustType::Primitive("i32".into())]),
+    );
+    let type1 = RustType::new_option(RustType::new_reference(true, vec));
+    assert_eq!(
+        type1.to_rust_code(None),
+        "::std::option::Option<&::std::vec::Vec<i32>>"
+    );
+}
+
+#[test]
+fn to_rust_code_unit() {
+    assert_eq!(RustType::unit().to_rust_code(None), "()");
+}
+
+#[test]
+fn display_path() {
+    let path = RustPath::from_good_str("qt_core::QString");
+    assert_eq!(path.to_string(), "qt_core::QString");
+    assert_eq!(format!("{}", RustPath::from_good_str("i32")), "i32");
+}
+
+#[test]
+fn display_type() {
+    let type1 = RustType::new_reference(true, common("qt_core::QString", None));
+    assert_eq!(format!("{}", type1), "&::qt_core::QString");
+
+    let type2 = common(
+        "std::vec::Vec",
+        Some(vec![common("qt_core::QString", None)]),
+    );
+    assert_eq!(type2.to_string(), "::std::vec::Vec<::qt_core::QString>");
+}
+
+#[test]
+fn path_from_str() {
+    let path: RustPath = "qt_core::q_string::QString".parse().unwrap();
+    assert_eq!(path.parts, vec!["qt_core", "q_string", "QString"]);
+    assert_eq!(path.crate_name(), "qt_core");
+    assert_eq!(path.last(), "QString");
+
+    let path2 = RustPath::from_str("i32").unwrap();
+    assert_eq!(path2.parts, vec!["i32"]);
+}
+
+#[test]
+fn path_from_str_errors() {
+    assert!(RustPath::from_str("").is_err());
+    assert!(RustPath::from_str("foo::").is_err());
+    assert!(RustPath::from_str("::foo").is_err());
+    assert!(RustPath::from_str("foo::::bar").is_err());
+}
+
+#[test]
+fn path_parent_and_join() {
+    let path = RustPath::from_good_str("a::b::c");
+    assert_eq!(path.parent().unwrap(), RustPath::from_good_str("a::b"));
+    assert_eq!(
+        path.parent().unwrap().join("d"),
+        RustPath::from_good_str("a::b::d")
+    );
+    assert!(RustPath::from_good_str("a").parent().is_err());
+}
+
+#[test]
+fn path_case_insensitive_clashes() {
+    let upper = RustPath::from_good_str("A::B");
+    let lower = RustPath::from_good_str("a::b");
+    assert!(upper.eq_ignore_ascii_case(&lower));
+    assert!(!upper.eq_ignore_ascii_case(&RustPath::from_good_str("a::b::c")));
+
+    let paths = vec![
+        upper.clone(),
+        RustPath::from_good_str("a::c"),
+        lower.clone(),
+        upper.clone(),
+    ];
+    assert_eq!(
+        case_insensitive_clashes(&paths),
+        vec![(&upper, &lower), (&lower, &upper)]
+    );
+}
+
+#[test]
+fn path_to_rust_code_escapes_keywords() {
+    let path = RustPath::from_good_str("foo::bar::type");
+    assert_eq!(path.to_rust_code(None), "::foo::bar::r#type");
+    assert_eq!(path.to_rust_code(Some("foo")), "crate::bar::r#type");
+    assert_eq!(path.full_name(None), "::foo::bar::type");
+
+    let path2 = RustPath::from_good_str("foo::type::Bar");
+    assert_eq!(path2.to_rust_code(None), "::foo::type_::Bar");
+    assert_eq!(path2.to_rust_code(Some("foo")), "crate::type_::Bar");
+
+    let path3 = RustPath::from_good_str("foo::lib::move::async");
+    assert_eq!(path3.to_rust_code(None), "::foo::lib_::move_::r#async");
+
+    let path4 = RustPath::from_good_str("foo::bar::self");
+    assert_eq!(path4.to_rust_code(None), "::foo::bar::self_");
+    assert_eq!(path4.to_rust_code(Some("foo")), "crate::bar::self_");
+}
+
+#[test]
+fn escape_rust_identifier_test() {
+    assert_eq!(&escape_rust_identifier("good"), "good");
+    assert_eq!(&escape_rust_identifier("type"), "r#type");
+    assert_eq!(&escape_rust_identifier("move"), "r#move");
+    assert_eq!(&escape_rust_identifier("async"), "r#async");
+    assert_eq!(&escape_rust_identifier("self"), "self_");
+    assert_eq!(&escape_rust_identifier("super"), "super_");
+    assert_eq!(&escape_rust_identifier("crate"), "crate_");
+    assert_eq!(&escape_rust_identifier("Self"), "Self_");
+}
+
+#[test]
+fn sanitize_rust_identifier_test() {
+    assert_eq!(&sanitize_rust_identifier("good", false), "good");
+    assert_eq!(&sanitize_rust_identifier("Self", false), "Self_");
+    assert_eq!(&sanitize_rust_identifier("mod", false), "mod_");
+    assert_eq!(&sanitize_rust_identifier("mod", true), "mod_");
+    assert_eq!(&sanitize_rust_identifier("main", false), "main");
+    assert_eq!(&sanitize_rust_identifier("main", true), "main_");
+    assert_eq!(&sanitize_rust_identifier("lib", false), "lib");
+    assert_eq!(&sanitize_rust_identifier("lib", true), "lib_");
+    assert_eq!(&sanitize_rust_identifier("async", false), "async_");
+}
+
+#[test]
+fn predicates() {
+    let unit = RustType::unit();
+    assert!(unit.is_unit());
+    assert!(!unit.is_ref());
+    assert!(!unit.is_ptr());
+    assert!(!unit.is_const());
+
+    let value = common("foo::Foo", None);
+    assert!(!value.is_unit());
+    assert!(!value.is_ref());
+    assert!(!value.is_ptr());
+    assert!(!value.is_const());
+
+    let const_ref = RustType::new_reference(true, value.clone());
+    assert!(const_ref.is_ref());
+    assert!(!const_ref.is_ptr());
+    assert!(const_ref.is_const());
+
+    let mut_ptr = RustType::new_pointer(false, value);
+    assert!(!mut_ptr.is_ref());
+    assert!(mut_ptr.is_ptr());
+    assert!(!mut_ptr.is_const());
+
+    let ptr_ptr = RustType::new_pointer(true, mut_ptr);
+    assert!(!ptr_ptr.is_ref());
+    assert!(ptr_ptr.is_ptr());
+    assert!(ptr_ptr.is_const());
+}
+
+#[test]
+fn dereference() {
+    let value = common("foo::Foo", None);
+
+    let const_ref = RustType::new_reference(true, value.clone());
+    assert_eq!(const_ref.dereference().unwrap(), value);
+
+    let const_ptr = RustType::new_pointer(true, value.clone());
+    assert_eq!(const_ptr.dereference().unwrap(), value);
+
+    let ptr_ptr = RustType::new_pointer(false, RustType::new_pointer(false, value.clone()));
+    assert_eq!(
+        ptr_ptr.dereference().unwrap(),
+        RustType::new_pointer(false, value.clone())
+    );
+
+    let option_ref = RustType::new_option(RustType::new_reference(false, value.clone()));
+    assert_eq!(option_ref.dereference().unwrap(), value);
+
+    assert!(value.dereference().is_err());
+    assert!(RustType::unit().dereference().is_err());
+    assert!(RustType::new_option(value).dereference().is_err());
+}
+
+#[test]
+fn slice() {
+    let type1 = RustType::new_slice_reference(true, RustType::Primitive("u8".into()));
+    assert_eq!(type1.to_rust_code(None), "&[u8]");
+
+    let type2 = RustType::new_slice_reference(false, common("qt_core::QString", None))
+        .with_lifetime("a".into());
+    assert_eq!(type2.to_rust_code(None), "&'a mut [::qt_core::QString]");
+    assert_eq!(
+        type2.to_rust_code(Some("qt_core")),
+        "&'a mut [crate::QString]"
+    );
+
+    let vec = common(
+        "std::vec::Vec",
+        Some(vec![RustType::Primitive("i32".into())]),
+    );
+    let type3 = RustType::new_slice_reference(true, vec);
+    assert_eq!(type3.to_rust_code(None), "&[::std::vec::Vec<i32>]");
+    assert!(!type3.can_be_same_as(&type1));
+    assert!(type1.can_be_same_as(&type1));
+}
+
+#[test]
+fn array() {
+    let type1 = RustType::Array {
+        item: Box::new(RustType::Primitive("i32".into())),
+        len: 4,
+    };
+    assert_eq!(type1.to_rust_code(None), "[i32; 4]");
+
+    let type2 = RustType::Array {
+        item: Box::new(RustType::Primitive("f64".into())),
+        len: 3,
+    };
+    let context = RustPath::from_good_str("foo");
+    assert_eq!(
+        type2
+            .caption(&context, RustTypeCaptionStrategy::Full)
+            .unwrap(),
+        "f64_array_3"
+    );
+    assert!(!type1.can_be_same_as(&type2));
+}
+
+#[test]
+fn tuple() {
+    let context = RustPath::from_good_str("foo");
+    let type1 = RustType::Tuple(vec![
+        RustType::Primitive("i32".into()),
+        RustType::Primitive("f64".into()),
+    ]);
+    assert_eq!(type1.to_rust_code(None), "(i32, f64)");
+    assert_eq!(
+        type1
+            .caption(&context, RustTypeCaptionStrategy::Full)
+            .unwrap(),
+        "tuple_i32_f64"
+    );
+
+    let type2 = RustType::Tuple(vec![
+        RustType::Primitive("bool".into()),
+        RustType::new_reference(true, common("qt_core::QString", None)).with_lifetime("a".into()),
+        RustType::Primitive("u8".into()),
+    ]);
+    assert_eq!(
+        type2.to_rust_code(None),
+        "(bool, &'a ::qt_core::QString, u8)"
+    );
+
+    let type3 = RustType::Tuple(vec![RustType::Primitive("i32".into())]);
+    assert_eq!(type3.to_rust_code(None), "(i32,)");
+    assert_eq!(RustType::unit().to_rust_code(None), "()");
+}
+
+#[test]
+fn lifetime_elision() {
+    let ref_a = RustType::new_reference(true, common("foo::Foo", None)).with_lifetime("a".into());
+    let ref_b = RustType::new_reference(true, common("foo::Foo", None)).with_lifetime("b".into());
+    let int = RustType::Primitive("i32".into());
+
+    // single input reference
+    assert!(can_elide_lifetimes(&[ref_a.clone(), int], &ref_a));
+    // multiple input references without reference in return type
+    assert!(can_elide_lifetimes(
+        &[ref_a.clone(), ref_b.clone()],
+        &RustType::unit()
+    ));
+    // multiple input references with reference in return type
+    assert!(!can_elide_lifetimes(
+        &[ref_a.clone(), ref_b.clone()],
+        &ref_a
+    ));
+    // a lifetime shared by two arguments
+    assert!(!can_elide_lifetimes(
+        &[ref_a.clone(), ref_a.clone()],
+        &RustType::unit()
+    ));
+    // return type refers to a lifetime not present in the argument
+    assert!(!can_elide_lifetimes(&[ref_a], &ref_b));
+    // static return value
+    let static_ref =
+        RustType::new_reference(true, common("foo::Bar", None)).with_lifetime("static".into());
+    assert!(!can_elide_lifetimes(&[], &static_ref));
+}
+
+#[test]
+fn collect_and_replace_lifetimes() {
+    // &'a Vec<&'b Foo>
+    let inner = RustType::new_reference(true, common("foo::Foo", None)).with_lifetime("b".into());
+    let t = RustType::new_reference(true, common("std::vec::Vec", Some(vec![inner])))
+        .with_lifetime("a".into());
+    assert_eq!(
+        t.collect_lifetimes(),
+        vec!["a".to_string(), "b".to_string()]
+    );
+    assert_eq!(t.to_rust_code(None), "&'a ::std::vec::Vec<&'b ::foo::Foo>");
+
+    let mut map = HashMap::new();
+    map.insert("a".to_string(), "x".to_string());
+    map.insert("b".to_string(), "y".to_string());
+    let renamed = t.replace_lifetimes(&map);
+    assert_eq!(
+        renamed.collect_lifetimes(),
+        vec!["x".to_string(), "y".to_string()]
+    );
+    assert_eq!(
+        renamed.to_rust_code(None),
+        "&'x ::std::vec::Vec<&'y ::foo::Foo>"
+    );
+
+    map.remove("b");
+    let partially_renamed = t.replace_lifetimes(&map);
+    assert_eq!(
+        partially_renamed.collect_lifetimes(),
+        vec!["x".to_string(), "b".to_string()]
+    );
+
+    let shared = RustType::Tuple(vec![t.clone(), t]);
+    assert_eq!(
+        shared.collect_lifetimes(),
+        vec!["a".to_string(), "b".to_string()]
+    );
+}
+
+#[test]
+fn with_lifetime_deep() {
+    let inner1 = RustType::new_reference(true, common("foo::Foo", None));
+    let inner2 = RustType::new_reference(false, common("foo::Bar", None)).with_lifetime("b".into());
+    let t = common(
+        "std::vec::Vec",
+        Some(vec![RustType::Tuple(vec![inner1, inner2])]),
+    );
+    assert_eq!(
+        t.with_lifetime_deep("a").to_rust_code(None),
+        "::std::vec::Vec<(&'a ::foo::Foo, &'b mut ::foo::Bar)>"
+    );
+
+    let pointer = RustType::new_pointer(true, common("foo::Foo", None));
+    assert_eq!(pointer.with_lifetime_deep("a"), pointer);
+
+    let value = RustType::Primitive("i32".into());
+    assert_eq!(value.with_lifetime_deep("a"), value);
+}
+
+#[test]
+fn pointer_like_mutability() {
+    let foo = common("foo::Foo", None);
+    let cases = vec![
+        (RustType::new_pointer(false, foo.clone()), "*mut ::foo::Foo"),
+        (
+            RustType::new_pointer(true, foo.clone()),
+            "*const ::foo::Foo",
+        ),
+        (RustType::new_reference(true, foo.clone()), "&::foo::Foo"),
+        (RustType::new_reference(false, foo), "&mut ::foo::Foo"),
+    ];
+    for (rust_type, code) in cases {
+        assert_eq!(rust_type.to_rust_code(None), code);
+        // mutability is preserved when a lifetime is added
+        let with_lifetime = rust_type.with_lifetime("a".into());
+        assert_eq!(with_lifetime.is_const(), rust_type.is_const());
+        assert_eq!(with_lifetime.is_ptr(), rust_type.is_ptr());
+    }
+}
+
+#[test]
+fn final_type_cstr_to_ptr() {
+    let ffi_type = RustType::new_pointer(true, common("std::os::raw::c_char", None));
+    let final_type =
+        RustFinalType::new(ffi_type.clone(), RustToFfiTypeConversion::CStrToPtr).unwrap();
+    assert_eq!(final_type.api_type().to_rust_code(None), "&str");
+    assert_eq!(final_type.ffi_type(), &ffi_type);
+    assert_eq!(final_type.conversion(), &RustToFfiTypeConversion::CStrToPtr);
+
+    let copy = final_type.clone();
+    assert_eq!(copy, final_type);
+    assert_eq!(copy.conversion(), &RustToFfiTypeConversion::CStrToPtr);
+
+    let not_a_pointer = common("std::os::raw::c_char", None);
+    assert!(RustFinalType::new(not_a_pointer, RustToFfiTypeConversion::CStrToPtr).is_err());
+}
+
+#[test]
+fn final_type_bool_to_int() {
+    let ffi_type = common("std::os::raw::c_int", None);
+    let final_type =
+        RustFinalType::new(ffi_type.clone(), RustToFfiTypeConversion::BoolToInt).unwrap();
+    assert_eq!(final_type.api_type(), &RustType::Primitive("bool".into()));
+    assert_eq!(final_type.ffi_type(), &ffi_type);
+    assert_eq!(final_type.conversion(), &RustToFfiTypeConversion::BoolToInt);
+    assert_eq!(final_type.clone(), final_type);
+}
+
+#[test]
+fn final_type_validate() {
+    let foo = common("foo::Foo", None);
+    let c_int = common("std::os::raw::c_int", None);
+
+    let valid = RustFinalType::new(
+        RustType::new_pointer(true, foo.clone()),
+        RustToFfiTypeConversion::RefToPtr { lifetime: None },
+    )
+    .unwrap();
+    assert!(valid.validate().is_ok());
+
+    let valid = RustFinalType::new(c_int.clone(), RustToFfiTypeConversion::BoolToInt).unwrap();
+    assert!(valid.validate().is_ok());
+
+    let ref_to_ref = RustFinalType::new(
+        RustType::new_reference(true, foo.clone()),
+        RustToFfiTypeConversion::RefToPtr { lifetime: None },
+    )
+    .unwrap();
+    assert!(ref_to_ref.validate().is_err());
+
+    let qflags_to_ptr = RustFinalType::new(
+        RustType::new_pointer(true, foo.clone()),
+        RustToFfiTypeConversion::QFlagsToUInt {
+            api_type: common("qt_core::QFlags", Some(vec![foo])),
+        },
+    )
+    .unwrap();
+    assert!(qflags_to_ptr.validate().is_err());
+
+    let bool_to_float = RustFinalType::new(
+        RustType::Primitive("f64".into()),
+        RustToFfiTypeConversion::BoolToInt,
+    )
+    .unwrap();
+    assert!(bool_to_float.validate().is_err());
+
+    let nested = RustFinalType::new(
+        c_int,
+        RustToFfiTypeConversion::RefTo(Box::new(RustToFfiTypeConversion::BoolToInt)),
+    )
+    .unwrap();
+    assert!(nested.validate().is_ok());
+}
+
+#[test]
+fn caption_or_void() {
+    let context = RustPath::from_good_str("foo");
+    let strategy = RustTypeCaptionStrategy::Full;
+
+    assert_eq!(RustType::unit().caption(&context, strategy).unwrap(), "");
+    assert_eq!(
+        RustType::unit()
+            .caption_or_void(&context, strategy)
+            .unwrap(),
+        "void"
+    );
+    assert_eq!(
+        RustType::Primitive("i32".into())
+            .caption_or_void(&context, strategy)
+            .unwrap(),
+        "i32"
+    );
+
+    let type1 = common("std::vec::Vec", Some(vec![RustType::unit()]));
+    assert_eq!(type1.caption(&context, strategy).unwrap(), "vec_void");
+}
+
+#[test]
+fn path_case_conversion() {
+    let path = RustPath::from_good_str("qt_core::QString");
+    assert_eq!(
+        path.to_snake_case(),
+        RustPath::from_good_str("qt_core::q_string")
+    );
+    assert_eq!(path.to_upper_camel_case(), path);
+
+    let path = RustPath::from_good_str("qt_xml::QXmlStreamReader");
+    assert_eq!(
+        path.to_snake_case(),
+        RustPath::from_good_str("qt_xml::q_xml_stream_reader")
+    );
+    assert_eq!(path.to_upper_camel_case(), path);
+
+    let path = RustPath::from_good_str("qt_core::q_string::toHtml");
+    assert_eq!(
+        path.to_snake_case(),
+        RustPath::from_good_str("qt_core::q_string::to_html")
+    );
+    assert_eq!(
+        path.to_upper_camel_case(),
+        RustPath::from_good_str("qt_core::q_string::ToHtml")
+    );
+
+    let path = RustPath::from_good_str("QtCore::set_value2");
+    assert_eq!(path.to_snake_case(), path);
+    assert_eq!(
+        path.to_upper_camel_case(),
+        RustPath::from_good_str("QtCore::SetValue2")
+    );
+}
+
+#[test]
+fn final_type_qflags_return_value() {
+    let ffi_type = common("std::os::raw::c_uint", None);
+    let api_type = common(
+        "qt_core::QFlags",
+        Some(vec![common("qt_core::AlignmentFlag", None)]),
+    );
+    let final_type = RustFinalType::new(
+        ffi_type.clone(),
+        RustToFfiTypeConversion::QFlagsToUInt {
+            api_type: api_type.clone(),
+        },
+    )
+    .unwrap();
+    assert_eq!(final_type.api_type(), &api_type);
+    assert_eq!(final_type.ffi_type(), &ffi_type);
+    assert!(final_type.validate().is_ok());
+
+    let cloned = final_type.clone();
+    assert_eq!(cloned, final_type);
+    assert_eq!(
+        cloned.conversion(),
+        &RustToFfiTypeConversion::QFlagsToUInt { api_type }
+    );
+}
+
+#[test]
+fn primitive_layout() {
+    assert_eq!(
+        RustType::Primitive("i32".into()).primitive_layout(),
+        Some((4, mem::align_of::<i32>()))
+    );
+    assert_eq!(
+        RustType::Primitive("u8".into()).primitive_layout(),
+        Some((1, 1))
+    );
+    assert_eq!(
+        common("std::os::raw::c_int", None).primitive_layout(),
+        Some((mem::size_of::<c_int>(), mem::align_of::<c_int>()))
+    );
+    let pointer_layout = Some((mem::size_of::<*const u8>(), mem::align_of::<*const u8>()));
+    assert_eq!(
+        RustType::new_pointer(true, common("foo::Foo", None)).primitive_layout(),
+        pointer_layout
+    );
+    assert_eq!(
+        RustType::new_pointer(false, RustType::Primitive("f64".into())).primitive_layout(),
+        pointer_layout
+    );
+
+    assert_eq!(
+        RustType::new_pointer(
+            true,
+            RustType::Slice(Box::new(RustType::Primitive("u8".into())))
+        )
+        .primitive_layout(),
+        None
+    );
+    assert_eq!(
+        RustType::new_reference(true, RustType::Primitive("i32".into())).primitive_layout(),
+        None
+    );
+    assert_eq!(common("foo::Foo", None).primitive_layout(), None);
+    assert_eq!(RustType::unit().primitive_layout(), None);
+}
+
+#[test]
+fn ffi_safe_types() {
+    let foo = common("foo::Foo", None);
+    assert!(RustType::new_pointer(true, foo.clone()).is_ffi_safe());
+    assert!(RustType::new_pointer(false, foo.clone()).is_ffi_safe());
+    assert!(RustType::unit().is_ffi_safe());
+    assert!(RustType::Primitive("i32".into()).is_ffi_safe());
+    assert!(common("std::os::raw::c_int", None).is_ffi_safe());
+    assert!(foo.is_ffi_safe());
+
+    let option_ref = common(
+        "std::option::Option",
+        Some(vec![RustType::new_reference(true, foo.clone())]),
+    );
+    assert!(option_ref.is_ffi_safe());
+
+    assert!(!RustType::new_reference(true, foo.clone()).is_ffi_safe());
+    assert!(!common("std::vec::Vec", Some(vec![foo.clone()])).is_ffi_safe());
+    assert!(!common(
+        "std::option::Option",
+        Some(vec![RustType::Primitive("i32".into())])
+    )
+    .is_ffi_safe());
+    assert!(!common("std::string::String", None).is_ffi_safe());
+    assert!(!RustType::Tuple(vec![foo.clone(), foo]).is_ffi_safe());
+    for name in &["str", "char", "i128", "u128"] {
+        assert!(!RustType::Primitive(name.to_string()).is_ffi_safe());
+    }
+}
+
+#[test]
+fn serde_round_trip() {
+    let type1 = common(
+        "std::option::Option",
+        Some(vec![RustType::new_reference(
+            true,
+            common("std::vec::Vec", Some(vec![common("foo::Foo", None)])),
+        )
+        .with_lifetime("a".into())]),
+    );
+    let json = serde_json::to_string(&type1).unwrap();
+    let type2: RustType = serde_json::from_str(&json).unwrap();
+    assert_eq!(type2, type1);
+    assert_eq!(type2.reference_lifetimes(), vec![Some("a")]);
+
+    let final_type = RustFinalType::new(
+        RustType::new_pointer(true, common("foo::Foo", None)),
+        RustToFfiTypeConversion::RefToPtr {
+            lifetime: Some("b".into()),
+        },
+    )
+    .unwrap();
+    let json = serde_json::to_string(&final_type).unwrap();
+    let final_type2: RustFinalType = serde_json::from_str(&json).unwrap();
+    assert_eq!(final_type2, final_type);
+
+    let path = RustPath::from_good_str("foo::bar::Baz");
+    let json = serde_json::to_string(&path).unwrap();
+    assert_eq!(serde_json::from_str::<RustPath>(&json).unwrap(), path);
+}
+
+#[test]
+fn stable_hash_ignores_lifetimes() {
+    let foo = common("foo::Foo", None);
+    let ref_a = RustType::new_reference(true, foo.clone()).with_lifetime("a".into());
+    let ref_b = RustType::new_reference(true, foo.clone()).with_lifetime("b".into());
+    let ref_none = RustType::new_reference(true, foo.clone());
+    let mut_ref = RustType::new_reference(false, foo).with_lifetime("a".into());
+    assert_eq!(ref_a.stable_hash(), ref_b.stable_hash());
+    assert_eq!(ref_a.stable_hash(), ref_none.stable_hash());
+    assert_ne!(ref_a.stable_hash(), mut_ref.stable_hash());
+
+    let vec_a = common("std::vec::Vec", Some(vec![ref_a]));
+    let vec_b = common("std::vec::Vec", Some(vec![ref_b]));
+    assert_eq!(vec_a.stable_hash(), vec_b.stable_hash());
+    // the hash must not change between runs and toolchain versions
+    assert_eq!(
+        vec_a.stable_hash(),
+        hash_bytes(b"::std::vec::Vec<&::foo::Foo>")
+    );
+}
+
+#[test]
+fn default_expressions() {
+    let foo = common("foo::Foo", None);
+    assert_eq!(
+        RustType::Primitive("i32".into()).default_expression(),
+        Some("0".to_string())
+    );
+    assert_eq!(
+        common("std::os::raw::c_uint", None).default_expression(),
+        Some("0".to_string())
+    );
+    assert_eq!(
+        RustType::bool().default_expression(),
+        Some("false".to_string())
+    );
+    assert_eq!(
+        RustType::new_pointer(true, foo.clone()).default_expression(),
+        Some("::std::ptr::null()".to_string())
+    );
+    assert_eq!(
+        RustType::new_pointer(false, foo.clone()).default_expression(),
+        Some("::std::ptr::null_mut()".to_string())
+    );
+    assert_eq!(
+        common(
+            "std::option::Option",
+            Some(vec![RustType::new_reference(true, foo.clone())])
+        )
+        .default_expression(),
+        Some("None".to_string())
+    );
+    assert_eq!(
+        common("std::vec::Vec", Some(vec![foo.clone()])).default_expression(),
+        Some("Vec::new()".to_string())
+    );
+    assert_eq!(
+        RustType::new_reference(true, foo.clone()).default_expression(),
+        None
+    );
+    assert_eq!(foo.default_expression(), None);
+}
+
+#[test]
+fn final_type_doc_signature() {
+    let qstring = common("qt_core::QString", None);
+    let final_type = RustFinalType::new(
+        RustType::new_pointer(true, qstring),
+        RustToFfiTypeConversion::RefToPtr { lifetime: None },
+    )
+    .unwrap();
+    let cpp_type = CppType::new_reference(true, CppType::Class(CppPath::from_good_str("QString")));
+    assert_eq!(
+        final_type.doc_signature(&cpp_type, Some("qt_core")),
+        "Rust `&crate::QString` (C++ `const QString&`)"
+    );
+    let text = final_type.doc_signature(&cpp_type, None);
+    assert!(text.contains("&::qt_core::QString"));
+    assert!(text.contains("const QString&"));
+}
+
+#[test]
+fn fold_replaces_primitives() {
+    let i32_type = RustType::Primitive("i32".into());
+    let type1 = common(
+        "std::collections::HashMap",
+        Some(vec![
+            i32_type.clone(),
+            common(
+                "std::vec::Vec",
+                Some(vec![RustType::new_pointer(true, i32_type.clone())]),
+            ),
+        ]),
+    );
+    let mut visited = 0;
+    let type2 = type1.fold(&mut |rust_type| {
+        visited += 1;
+        if rust_type == &RustType::Primitive("i32".into()) {
+            Some(RustType::Primitive("i64".into()))
+        } else {
+            None
+        }
+    });
+    assert_eq!(
+        type2.to_rust_code(None),
+        "::std::collections::HashMap<i64, ::std::vec::Vec<*const i64>>"
+    );
+    assert_eq!(visited, 5);
+
+    // nested types are folded before their parents
+    let mut order = Vec::new();
+    type1.fold(&mut |rust_type| {
+        order.push(rust_type.to_rust_code(None));
+        None
+    });
+    assert_eq!(order[0], "i32");
+    assert_eq!(order.last().unwrap(), &type1.to_rust_code(None));
+}
+
+#[test]
+fn map_paths_replaces_crate_name() {
+    let type1 = common(
+        "std::vec::Vec",
+        Some(vec![RustType::new_reference(
+            true,
+            common(
+                "old_crate::Foo",
+                Some(vec![RustType::new_pointer(
+                    false,
+                    common("old_crate::a::Bar", None),
+                )]),
+            ),
+        )]),
+    );
+    let type2 = type1.map_paths(&|path: &RustPath| {
+        if path.crate_name() == "old_crate" {
+            let mut parts = path.parts().to_vec();
+            parts[0] = "new_crate".to_string();
+            RustPath::from_parts(parts)
+        } else {
+            path.clone()
+        }
+    });
+    assert_eq!(
+        type2.to_rust_code(None),
+        "::std::vec::Vec<&::new_crate::Foo<*mut ::new_crate::a::Bar>>"
+    );
+    assert!(!type2.to_rust_code(None).contains("old_crate"));
+}
+
+#[test]
+fn path_try_from_parts() {
+    let path = RustPath::try_from_parts(vec!["foo".into(), "Bar".into()]).unwrap();
+    assert_eq!(path, RustPath::from_good_str("foo::Bar"));
+
+    let err = RustPath::try_from_parts(Vec::new()).unwrap_err();
+    assert_eq!(err.to_string(), "RustPath can't be empty");
+
+    let err = RustPath::try_from_parts(vec!["foo".into(), String::new()]).unwrap_err();
+    assert!(err.to_string().starts_with("RustPath item can't be empty"));
+
+    assert!(RustPath::from_str("").is_err());
+    assert!(RustPath::from_str("foo::::Bar").is_err());
+}
+
+#[test]
+#[should_panic(expected = "RustPath can't be empty")]
+fn path_from_empty_parts() {
+    RustPath::from_parts(Vec::new());
+}
+
+#[test]
+fn nullable_reference_validation() {
+    let foo = common("foo::Foo", None);
+    let type1 = RustType::new_nullable_reference(true, foo.clone());
+    assert_eq!(
+        type1.to_rust_code(None),
+        "::std::option::Option<&::foo::Foo>"
+    );
+    assert!(type1.validate_option().is_ok());
+    assert!(foo.validate_option().is_ok());
+
+    let type2 = RustType::new_option(RustType::Primitive("i32".into()));
+    assert!(type2.validate_option().is_err());
+
+    let type3 = common("std::vec::Vec", Some(vec![type2]));
+    assert!(type3.validate_option().is_err());
+}
+
+#[test]
+fn path_disambiguate() {
+    let path = RustPath::from_good_str("foo::Foo");
+    let mut taken = HashSet::new();
+    assert_eq!(path.disambiguate(&taken), path);
+
+    taken.insert("Foo".to_string());
+    assert_eq!(
+        path.disambiguate(&taken),
+        RustPath::from_good_str("foo::Foo2")
+    );
+
+    taken.insert("Foo2".to_string());
+    taken.insert("Bar".to_string());
+    assert_eq!(
+        path.disambiguate(&taken),
+        RustPath::from_good_str("foo::Foo3")
+    );
+}
+
+#[test]
+fn into_and_strip_option() {
+    let reference = RustType::new_reference(true, common("foo::Foo", None));
+    let option = reference.clone().into_option();
+    assert_eq!(
+        option,
+        RustType::new_nullable_reference(true, common("foo::Foo", None))
+    );
+    assert_eq!(option.clone().into_option(), option);
+    assert_eq!(option.strip_option(), reference);
+    assert_eq!(reference.strip_option(), reference);
+
+    let ptr = common(
+        "cpp_core::Ref",
+        Some(vec![common("qt_core::QString", None)]),
+    );
+    assert_eq!(ptr.clone().into_option().strip_option(), ptr);
+}
+
+#[test]
+#[should_panic]
+fn into_option_of_value() {
+    RustType::Primitive("i32".into()).into_option();
+}
+
+#[test]
+fn referenced_crates() {
+    let type1 = common(
+        "std::option::Option",
+        Some(vec![common(
+            "std::vec::Vec",
+            Some(vec![RustType::new_pointer(
+                true,
+                common("qt_core::QString", None),
+            )]),
+        )]),
+    );
+    let crates = type1.referenced_crates(None);
+    assert_eq!(
+        crates.into_iter().collect::<Vec<_>>(),
+        vec!["qt_core".to_string(), "std".to_string()]
+    );
+
+    let crates = type1.referenced_crates(Some("qt_core"));
+    assert_eq!(
+        crates.into_iter().collect::<Vec<_>>(),
+        vec!["std".to_string()]
+    );
+
+    assert!(RustType::Primitive("i32".into())
+        .referenced_crates(None)
+        .is_empty());
+}
+
+fn trait_object(kind: RustTraitObjectKind) -> RustType {
+    RustType::TraitObject {
+        kind,
+        trait_type: RustCommonType {
+            path: RustPath::from_good_str("foo::Foo"),
+            generic_arguments: None,
+        },
+    }
+}
+
+#[test]
+fn trait_object_reference() {
+    let kind = RustTraitObjectKind::Reference {
+        lifetime: Some("a".into()),
+        is_const: true,
+    };
+    let t = trait_object(kind);
+    assert_eq!(t.to_rust_code(None), "&'a dyn ::foo::Foo");
+    assert_eq!(t.to_rust_code(Some("foo")), "&'a dyn crate::Foo");
+    assert_eq!(t.lifetime(), Some("a"));
+    assert_eq!(t.without_lifetimes().to_rust_code(None), "&dyn ::foo::Foo");
+    assert!(!t.is_ffi_safe());
+
+    let kind = RustTraitObjectKind::Reference {
+        lifetime: None,
+        is_const: false,
+    };
+    let t = trait_object(kind).with_lifetime("b".into());
+    assert_eq!(t.to_rust_code(None), "&'b mut dyn ::foo::Foo");
+}
+
+#[test]
+fn trait_object_box() {
+    let t = trait_object(RustTraitObjectKind::Box);
+    assert_eq!(t.to_rust_code(None), "::std::boxed::Box<dyn ::foo::Foo>");
+    assert_eq!(t.lifetime(), None);
+    assert_eq!(t.with_lifetime("a".into()), t);
+    assert_eq!(
+        t.referenced_crates(None).into_iter().collect::<Vec<_>>(),
+        vec!["foo".to_string()]
+    );
+}
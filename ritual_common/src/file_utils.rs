@@ -1,6 +1,7 @@
 //! Various utilities for working with files
 
 use crate::errors::{bail, err_msg, format_err, Result, ResultExt};
+use crate::utils::hash_bytes;
 use log::trace;
 use std::ffi::{OsStr, OsString};
 use std::fs;
@@ -287,6 +288,35 @@ pub fn save_toml_table<P: AsRef<Path>>(path: P, data: &toml::Value) -> Result<()
     Ok(())
 }
 
+/// Returns `hash_bytes` digest of the content of the file.
+pub fn hash_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    let data = fs::read(path.as_ref())
+        .with_context(|_| format!("Failed to read file: {:?}", path.as_ref()))?;
+    Ok(hash_bytes(&data))
+}
+
+/// Writes `contents` to the file at `path` unless the file already has
+/// exactly this content, so that its modification time is preserved.
+/// Parent directories are created if necessary.
+/// Returns true if the file was written.
+pub fn write_if_changed<P: AsRef<Path>>(path: P, contents: &str) -> Result<bool> {
+    let path = path.as_ref();
+    if path.is_file()
+        && fs::read(path).with_context(|_| format!("Failed to read file: {:?}", path))?
+            == contents.as_bytes()
+    {
+        trace!("File is unchanged: {}", path.display());
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            create_dir_all(parent)?;
+        }
+    }
+    fs::write(path, contents).with_context(|_| format!("Failed to write file: {:?}", path))?;
+    Ok(true)
+}
+
 /// A wrapper over `std::fs::create_dir` with better error reporting
 pub fn create_dir<P: AsRef<Path>>(path: P) -> Result<()> {
     fs::create_dir(path.as_ref())
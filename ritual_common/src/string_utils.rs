@@ -1,6 +1,7 @@
 //! Various utilities for string operations.
 
 use itertools::Itertools;
+use std::fmt::Display;
 
 /// Iterator over words in a camel-case
 /// or snake-case string.
@@ -158,6 +159,35 @@ impl<'a> CaseOperations for Vec<&'a str> {
     }
 }
 
+/// Join items of a collection into a human-readable list
+pub trait JoinWithConjunction {
+    /// Joins the items with `separator`, placing `conjunction` before the last item,
+    /// e.g. `"A, B, or C"` for `separator = ", "` and `conjunction = "or"`.
+    /// Two items are joined as `"A or B"`, and a single item is returned as is.
+    fn join_with_conjunction(self, separator: &str, conjunction: &str) -> String;
+}
+
+impl<T: IntoIterator> JoinWithConjunction for T
+where
+    T::Item: Display,
+{
+    fn join_with_conjunction(self, separator: &str, conjunction: &str) -> String {
+        let items = self.into_iter().map(|x| x.to_string()).collect_vec();
+        match items.len() {
+            0 => String::new(),
+            1 => items[0].clone(),
+            2 => format!("{} {} {}", items[0], conjunction, items[1]),
+            len => format!(
+                "{}{}{} {}",
+                items[..len - 1].join(separator),
+                separator,
+                conjunction,
+                items[len - 1]
+            ),
+        }
+    }
+}
+
 pub fn trim_slice<T, F>(slice: &[T], mut f: F) -> &[T]
 where
     F: FnMut(&T) -> bool,
@@ -1,6 +1,25 @@
-use crate::string_utils::{CaseOperations, WordIterator};
+use crate::file_utils::{hash_file, write_if_changed};
+use crate::string_utils::{CaseOperations, JoinWithConjunction, WordIterator};
+use crate::utils::{
+    add_env_path_item, add_secret, add_to_multihash, add_to_multihash_set, dylib_file_name,
+    find_executable, get_command_output, get_command_output_allow_failure, hash_bytes, is_dry_run,
+    redact_secrets, remove_from_multihash, run_command, run_command_and_capture_combined,
+    run_command_and_capture_output, run_command_and_capture_output_limited, run_command_in_dir,
+    run_command_streaming, run_command_with_env, run_command_with_retries, run_command_with_stdin,
+    run_command_with_timeout, set_dry_run, CommandFailed, CommandOutput, Inspect, MultiProgress,
+    ProgressBar, TempDir, OUTPUT_TRUNCATED_MARKER,
+};
 use itertools::Itertools;
-use std::path::PathBuf;
+use lazy_static::lazy_static;
+use std::cell::Cell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, MutexGuard, Once};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[test]
 fn join() {
@@ -124,3 +143,915 @@ fn case_operations() {
     let v4 = vec!["count", "1"];
     assert_eq!(v4.to_snake_case(), "count1");
 }
+
+lazy_static! {
+    /// Lock held by tests that depend on progress bars being enabled
+    /// or that change it.
+    static ref PROGRESS_BAR_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Acquires `PROGRESS_BAR_LOCK` and enables progress bars.
+fn lock_progress_bars() -> MutexGuard<'static, ()> {
+    let lock = PROGRESS_BAR_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    ProgressBar::set_enabled(true);
+    lock
+}
+
+#[test]
+fn progress_bar_render() {
+    let progress_bar = ProgressBar::new(10, "test");
+    assert_eq!(progress_bar.render(), "test: 0 / 10 [00:00 elapsed]");
+
+    progress_bar.add(5);
+    let line = progress_bar.render();
+    assert!(line.starts_with("test: 5 / 10 [00:00 elapsed"));
+    assert!(line.ends_with(" left]"));
+
+    progress_bar.add(5);
+    assert_eq!(progress_bar.render(), "test: 10 / 10 [00:00 elapsed]");
+}
+
+#[test]
+fn progress_bar_terminal_mode() {
+    let _lock = lock_progress_bars();
+    let progress_bar = ProgressBar::new_in_memory(4, "test", true);
+    progress_bar.set_redraw_interval(Duration::from_secs(0));
+    progress_bar.add(1);
+    let output = progress_bar.output();
+    assert!(output.contains("\rtest: 1 / 4 ["));
+    assert!(!output.contains('\n'));
+}
+
+#[test]
+fn progress_bar_non_terminal_mode() {
+    let _lock = lock_progress_bars();
+    let progress_bar = ProgressBar::new_in_memory(100, "test", false);
+    for _ in 0..100 {
+        progress_bar.add(1);
+    }
+    let output = progress_bar.output();
+    assert!(!output.contains('\r'));
+    let lines = output.lines().collect::<Vec<_>>();
+    // initial line and one line per 10%
+    assert_eq!(lines.len(), 11);
+    assert!(lines[0].starts_with("test: 0 / 100 ["));
+    assert!(lines[1].starts_with("test: 10 / 100 ["));
+    assert!(lines[10].starts_with("test: 100 / 100 ["));
+    assert!(output.ends_with('\n'));
+}
+
+#[test]
+fn progress_bar_spinner() {
+    let _lock = lock_progress_bars();
+    let progress_bar = ProgressBar::new_spinner_in_memory("test", true);
+    progress_bar.set_redraw_interval(Duration::from_secs(0));
+    assert_eq!(progress_bar.render(), "test: | [00:00 elapsed]");
+    progress_bar.tick();
+    assert_eq!(progress_bar.render(), "test: / [00:00 elapsed]");
+    progress_bar.tick();
+    assert_eq!(progress_bar.render(), "test: - [00:00 elapsed]");
+    progress_bar.add(3);
+    assert_eq!(progress_bar.render(), "test: - 3 [00:00 elapsed]");
+    assert!(progress_bar
+        .output()
+        .contains("\rtest: / [00:00 elapsed]\r"));
+
+    progress_bar.finish();
+    assert!(progress_bar.output().ends_with("test: done [in 00:00]\n"));
+}
+
+#[test]
+fn progress_bar_spinner_non_terminal_mode() {
+    let _lock = lock_progress_bars();
+    let progress_bar = ProgressBar::new_spinner_in_memory("test", false);
+    for _ in 0..10 {
+        progress_bar.tick();
+    }
+    progress_bar.finish();
+    assert_eq!(
+        progress_bar.output(),
+        "test: | [00:00 elapsed]\ntest: done [in 00:00]\n"
+    );
+}
+
+#[test]
+fn progress_bar_child() {
+    let _lock = lock_progress_bars();
+    let parent = ProgressBar::new_in_memory(2, "parent", true);
+    parent.set_redraw_interval(Duration::from_secs(0));
+    assert_eq!(parent.lines().len(), 1);
+
+    let child = parent.add_child(3, "child");
+    let lines = parent.lines();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("parent: 0 / 2 ["));
+    assert!(lines[1].starts_with("child: 0 / 3 ["));
+    assert_eq!(child.lines(), lines);
+
+    child.add(2);
+    assert!(parent.lines()[1].starts_with("child: 2 / 3 ["));
+    // the child line is redrawn below the parent line
+    assert!(parent.output().contains("\r\x1b[2Kchild: 2 / 3 ["));
+
+    child.add(1);
+    child.finish();
+    let lines = parent.lines();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].starts_with("parent: 1 / 2 ["));
+    assert!(!parent.output().contains("child: 3 / 3 [done"));
+
+    parent.add_child(1, "second child").finish();
+    parent.finish();
+    assert!(parent.lines().is_empty());
+    assert!(parent.output().contains("parent: 2 / 2 [done in 00:00]\n"));
+}
+
+#[test]
+fn progress_bar_child_non_terminal_mode() {
+    let _lock = lock_progress_bars();
+    let parent = ProgressBar::new_in_memory(1, "parent", false);
+    let child = parent.add_child(1, "child");
+    child.add(1);
+    drop(child);
+    parent.finish();
+
+    let output = parent.output();
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 6);
+    assert!(lines[0].starts_with("parent: 0 / 1 ["));
+    assert!(lines[1].starts_with("child: 0 / 1 ["));
+    assert!(lines[2].starts_with("child: 1 / 1 [00:00 elapsed"));
+    assert!(lines[3].starts_with("child: 1 / 1 [done"));
+    assert!(lines[4].starts_with("parent: 1 / 1 [00:00 elapsed"));
+    assert!(lines[5].starts_with("parent: 1 / 1 [done"));
+}
+
+#[test]
+fn multi_progress() {
+    let _lock = lock_progress_bars();
+    let multi = MultiProgress::new_in_memory(true);
+    let bars = (0..2)
+        .map(|i| multi.add(10, format!("worker {}", i)))
+        .collect::<Vec<_>>();
+    let threads = bars
+        .iter()
+        .map(|bar| {
+            bar.set_redraw_interval(Duration::from_secs(0));
+            let bar = bar.clone();
+            thread::spawn(move || {
+                for _ in 0..10 {
+                    bar.add(1);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in threads {
+        handle.join().unwrap();
+    }
+
+    let render = multi.render();
+    assert_eq!(render.matches("worker 0:").count(), 1);
+    assert_eq!(render.matches("worker 1:").count(), 1);
+    let lines = multi.lines();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("worker 0: 10 / 10 ["));
+    assert!(lines[1].starts_with("worker 1: 10 / 10 ["));
+
+    bars[0].finish();
+    assert_eq!(multi.lines().len(), 1);
+    assert!(multi
+        .output()
+        .contains("worker 0: 10 / 10 [done in 00:00]\n"));
+}
+
+#[test]
+fn progress_bar_finish() {
+    let _lock = lock_progress_bars();
+    let progress_bar = ProgressBar::new_in_memory(2, "test", true);
+    progress_bar.add(2);
+    progress_bar.finish();
+    let output = progress_bar.output();
+    assert!(output.ends_with("test: 2 / 2 [done in 00:00]\n"));
+
+    // nothing is printed after finishing
+    progress_bar.add(1);
+    progress_bar.finish();
+    assert_eq!(progress_bar.output(), output);
+}
+
+#[test]
+fn progress_bar_set_message_and_position() {
+    let _lock = lock_progress_bars();
+    let progress_bar = ProgressBar::new_in_memory(10, "first", true);
+    progress_bar.set_redraw_interval(Duration::from_secs(0));
+    progress_bar.set_position(3);
+    assert!(progress_bar.render().starts_with("first: 3 / 10 ["));
+
+    progress_bar.set_message("second");
+    assert!(progress_bar.render().starts_with("second: 3 / 10 ["));
+    assert!(progress_bar.output().contains("second: 3 / 10 ["));
+
+    progress_bar.set_position(20);
+    assert!(progress_bar.render().starts_with("second: 10 / 10 ["));
+}
+
+#[test]
+fn progress_bar_throttling() {
+    let _lock = lock_progress_bars();
+    let progress_bar = ProgressBar::new_in_memory(100, "test", true);
+    progress_bar.set_redraw_interval(Duration::from_secs(3600));
+    for _ in 0..50 {
+        progress_bar.add(1);
+    }
+    // only the initial state is drawn
+    assert_eq!(progress_bar.output().matches("test: ").count(), 1);
+
+    progress_bar.set_redraw_interval(Duration::from_secs(0));
+    progress_bar.add(1);
+    assert_eq!(progress_bar.output().matches("test: ").count(), 2);
+    assert!(progress_bar.output().contains("test: 51 / 100 ["));
+
+    // the final state is always drawn
+    progress_bar.set_redraw_interval(Duration::from_secs(3600));
+    progress_bar.add(49);
+    progress_bar.finish();
+    assert!(progress_bar
+        .output()
+        .ends_with("test: 100 / 100 [done in 00:00]\n"));
+}
+
+#[test]
+fn progress_bar_visual_bar() {
+    let _lock = lock_progress_bars();
+    let progress_bar = ProgressBar::new_in_memory(100, "test", true);
+    progress_bar.set_bar_columns(Some(79));
+    progress_bar.set_position(50);
+    let line = progress_bar.render();
+    assert_eq!(line.len(), 78);
+    assert!(line.starts_with("test: ["));
+    let bar = &line[line.find('[').unwrap() + 1..line.find(']').unwrap()];
+    assert_eq!(bar.len(), 28);
+    assert_eq!(bar.matches('#').count(), 14);
+    assert_eq!(bar.matches('-').count(), 14);
+    assert!(line.contains("] 50% 50 / 100 ["));
+
+    // not enough space for the bar
+    progress_bar.set_bar_columns(Some(10));
+    assert!(progress_bar.render().starts_with("test: 50 / 100 ["));
+
+    progress_bar.set_bar_columns(None);
+    assert!(progress_bar.render().starts_with("test: 50 / 100 ["));
+}
+
+struct CapturingLogger {
+    messages: Mutex<Vec<String>>,
+}
+
+impl log::Log for CapturingLogger {
+    fn enabled(&self, _metadata: &log::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if self.enabled(record.metadata()) {
+            self.messages
+                .lock()
+                .unwrap()
+                .push(format!("{}", record.args()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+lazy_static! {
+    static ref LOGGER: CapturingLogger = CapturingLogger {
+        messages: Mutex::new(Vec::new()),
+    };
+}
+
+static LOGGER_INIT: Once = Once::new();
+
+/// Installs the capturing logger and returns messages logged so far
+/// that start with `prefix`. Tests run in parallel, so messages
+/// from other tests may be present as well.
+fn captured_log_messages(prefix: &str) -> Vec<String> {
+    LOGGER_INIT.call_once(|| {
+        log::set_logger(&*LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+    });
+    LOGGER
+        .messages
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|m| m.starts_with(prefix))
+        .cloned()
+        .collect()
+}
+
+#[test]
+fn progress_bar_log_output() {
+    let _lock = lock_progress_bars();
+    captured_log_messages("");
+
+    let progress_bar = ProgressBar::new_with_log(10, "log test");
+    progress_bar.add(5);
+    assert_eq!(progress_bar.output(), "");
+    progress_bar.finish();
+
+    let messages = captured_log_messages("log test: ");
+    assert_eq!(messages.len(), 3);
+    assert!(messages[0].starts_with("log test: 0 / 10 ["));
+    assert!(messages[1].starts_with("log test: 5 / 10 ["));
+    assert_eq!(messages[2], "log test: 5 / 10 [done in 00:00]");
+}
+
+lazy_static! {
+    /// Lock held by tests that depend on dry run mode being disabled
+    /// or that change it.
+    static ref COMMAND_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn lock_commands() -> MutexGuard<'static, ()> {
+    COMMAND_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(unix)]
+#[test]
+fn run_command_with_timeout_kills_process() {
+    let _lock = lock_commands();
+    let start_time = Instant::now();
+    let mut command = Command::new("sleep");
+    command.arg("10");
+    let err = run_command_with_timeout(&mut command, Duration::from_millis(100)).unwrap_err();
+    assert!(err.to_string().contains("timed out after 100ms"));
+    assert!(err.to_string().contains("sleep"));
+    assert!(start_time.elapsed() < Duration::from_secs(5));
+
+    let mut command = Command::new("true");
+    assert!(run_command_with_timeout(&mut command, Duration::from_secs(10)).is_ok());
+}
+
+#[cfg(unix)]
+#[test]
+fn run_command_exit_code() {
+    let _lock = lock_commands();
+    let mut command = Command::new("sh");
+    command.arg("-c").arg("exit 2");
+    let err = run_command(&mut command).unwrap_err();
+    let command_failed = err.downcast_ref::<CommandFailed>().unwrap();
+    assert_eq!(command_failed.exit_code(), Some(2));
+    assert!(err
+        .to_string()
+        .starts_with("command failed with exit code: 2: "));
+
+    let err = get_command_output(&mut command).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<CommandFailed>().unwrap().exit_code(),
+        Some(2)
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn get_command_output_nonzero_exit_code() {
+    let _lock = lock_commands();
+    let mut command = Command::new("sh");
+    command.arg("-c").arg("echo out; echo err >&2; exit 1");
+    let output = get_command_output_allow_failure(&mut command).unwrap();
+    assert_eq!(output.exit_code(), Some(1));
+    assert!(!output.is_success());
+    assert_eq!(output.stdout, "out\n");
+    assert_eq!(output.stderr, "err\n");
+
+    let mut command = Command::new("echo");
+    command.arg("ok");
+    let output = get_command_output_allow_failure(&mut command).unwrap();
+    assert!(output.is_success());
+    assert_eq!(output.stdout_trimmed(), "ok");
+}
+
+#[test]
+fn command_not_found() {
+    let _lock = lock_commands();
+    let program = "ritual_nonexistent_executable";
+    let err = run_command(&mut Command::new(program)).unwrap_err();
+    assert!(err.downcast_ref::<CommandFailed>().is_none());
+    let text = err.to_string();
+    assert!(text.contains("not found"));
+    assert!(text.contains(&format!("`{}`", program)));
+
+    let err = get_command_output(Command::new(program).arg("--version")).unwrap_err();
+    assert!(err.to_string().contains("not found"));
+}
+
+#[cfg(unix)]
+#[test]
+fn run_command_in_dir_and_with_env() {
+    let _lock = lock_commands();
+    let dir = env::temp_dir();
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg("[ \"$(pwd -P)\" = \"$(cd \"$1\" && pwd -P)\" ]")
+        .arg("sh")
+        .arg(&dir);
+    run_command_in_dir(&mut command, &dir).unwrap();
+    assert!(run_command_in_dir(&mut command, Path::new("/")).is_err());
+
+    let mut vars = HashMap::new();
+    vars.insert("RITUAL_TEST_VAR".to_string(), "value".to_string());
+    let mut command = Command::new("sh");
+    command.arg("-c").arg("[ \"$RITUAL_TEST_VAR\" = value ]");
+    run_command_with_env(&mut command, &vars).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn run_command_streaming_lines() {
+    let _lock = lock_commands();
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg("echo one; echo two >&2; echo three; echo four >&2; exit 3");
+    let mut stdout_lines = Vec::new();
+    let mut stderr_lines = Vec::new();
+    let status = run_command_streaming(
+        &mut command,
+        |line| stdout_lines.push(line.to_string()),
+        |line| stderr_lines.push(line.to_string()),
+    )
+    .unwrap();
+    assert_eq!(status.code(), Some(3));
+    assert_eq!(stdout_lines, vec!["one", "three"]);
+    assert_eq!(stderr_lines, vec!["two", "four"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn run_command_combined_output() {
+    let _lock = lock_commands();
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg("echo one; echo two >&2; echo three; exit 1");
+    let output = run_command_and_capture_combined(&mut command).unwrap();
+    assert_eq!(output.status, 1);
+    assert_eq!(output.stdout, "one\ntwo\nthree\n");
+    assert_eq!(output.stderr, "");
+}
+
+#[test]
+fn command_output_combined() {
+    let output = |stdout: &str, stderr: &str| CommandOutput {
+        status: 0,
+        stdout: stdout.to_string(),
+        stderr: stderr.to_string(),
+    };
+    assert_eq!(output("out\n", "").combined(), "out\n");
+    assert_eq!(output("", "err\n").combined(), "err\n");
+    assert_eq!(
+        output("out\n", "err\n").combined(),
+        "out\n--- stderr ---\nerr\n"
+    );
+    assert_eq!(output("out", "err").combined(), "out\n--- stderr ---\nerr");
+}
+
+#[test]
+fn command_output_helpers() {
+    let success = CommandOutput {
+        status: 0,
+        stdout: "result\n\n".to_string(),
+        stderr: String::new(),
+    };
+    assert_eq!(success.exit_code(), Some(0));
+    assert_eq!(success.stdout_trimmed(), "result");
+    assert_eq!(success.clone().into_result().unwrap(), success);
+
+    let failure = CommandOutput {
+        status: 2,
+        stdout: "partial result\n".to_string(),
+        stderr: "error message\n".to_string(),
+    };
+    assert_eq!(failure.exit_code(), Some(2));
+    assert_eq!(failure.stdout_trimmed(), "partial result");
+    let err = failure.into_result().unwrap_err().to_string();
+    assert!(err.contains("status 2"));
+    assert!(err.contains("partial result"));
+    assert!(err.contains("error message"));
+
+    let terminated = CommandOutput {
+        status: -1,
+        stdout: String::new(),
+        stderr: String::new(),
+    };
+    assert_eq!(terminated.exit_code(), None);
+    assert!(terminated.into_result().is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn run_command_with_stdin_cat() {
+    let _lock = lock_commands();
+    let output = run_command_with_stdin(&mut Command::new("cat"), b"line1\nline2\n").unwrap();
+    assert!(output.is_success());
+    assert_eq!(output.stdout, "line1\nline2\n");
+
+    // larger than a pipe buffer
+    let input = "0123456789\n".repeat(100_000);
+    let output = run_command_with_stdin(&mut Command::new("cat"), input.as_bytes()).unwrap();
+    assert_eq!(output.stdout, input);
+}
+
+#[cfg(unix)]
+#[test]
+fn find_executable_in_path() {
+    let sh = find_executable("sh").unwrap();
+    assert!(sh.is_absolute());
+    assert!(sh.ends_with("sh"));
+    assert!(find_executable("ritual_nonexistent_executable_name").is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn run_command_with_retries_succeeds_eventually() {
+    let _lock = lock_commands();
+    let attempts = Cell::new(0);
+    let factory = || {
+        attempts.set(attempts.get() + 1);
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(if attempts.get() < 3 {
+            "exit 1"
+        } else {
+            "exit 0"
+        });
+        command
+    };
+    run_command_with_retries(factory, 5, Duration::from_millis(1)).unwrap();
+    assert_eq!(attempts.get(), 3);
+
+    attempts.set(0);
+    let err = run_command_with_retries(factory, 2, Duration::from_millis(1)).unwrap_err();
+    assert!(err.downcast_ref::<CommandFailed>().is_some());
+    assert_eq!(attempts.get(), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn command_duration_is_logged() {
+    let _lock = lock_commands();
+    captured_log_messages("");
+    let mut command = Command::new("sh");
+    command.arg("-c").arg("true # duration test");
+    run_command(&mut command).unwrap();
+    let messages = captured_log_messages("Command finished in ");
+    assert!(messages
+        .iter()
+        .any(|m| m.contains("exit code: 0") && m.contains("duration test")));
+}
+
+#[cfg(unix)]
+#[test]
+fn dry_run() {
+    let _lock = lock_commands();
+    let marker = env::temp_dir().join("ritual_dry_run_test_marker");
+    if marker.exists() {
+        fs::remove_file(&marker).unwrap();
+    }
+    let mut command = Command::new("touch");
+    command.arg(&marker);
+
+    set_dry_run(true);
+    assert!(is_dry_run());
+    let result = run_command(&mut command);
+    let timeout_result = run_command_with_timeout(&mut command, Duration::from_secs(10));
+    let stdout = get_command_output(&mut command);
+    let status = run_command_streaming(&mut command, |_| {}, |_| {});
+    let outputs = vec![
+        run_command_and_capture_output(&mut command),
+        run_command_and_capture_output_limited(&mut command, 10),
+        run_command_with_stdin(&mut command, b"input"),
+        run_command_and_capture_combined(&mut command),
+        get_command_output_allow_failure(&mut command),
+    ];
+    set_dry_run(false);
+
+    result.unwrap();
+    timeout_result.unwrap();
+    assert_eq!(stdout.unwrap(), "");
+    assert!(status.unwrap().success());
+    for output in outputs {
+        let output = output.unwrap();
+        assert!(output.is_success());
+        assert_eq!(output.stdout, "");
+    }
+    assert!(!marker.exists());
+
+    assert!(!is_dry_run());
+    run_command(&mut command).unwrap();
+    assert!(marker.exists());
+    fs::remove_file(&marker).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn secrets_are_redacted() {
+    let _lock = lock_commands();
+    captured_log_messages("");
+    add_secret("ritual_test_secret_token");
+    assert_eq!(
+        redact_secrets("Authorization: ritual_test_secret_token"),
+        "Authorization: ***"
+    );
+
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg("[ \"$1\" = ritual_test_secret_token ]")
+        .arg("sh")
+        .arg("ritual_test_secret_token");
+    // the command receives the real value
+    run_command(&mut command).unwrap();
+
+    let messages = captured_log_messages("Executing command: ");
+    assert!(messages
+        .iter()
+        .all(|m| !m.contains("ritual_test_secret_token")));
+    assert!(messages.iter().any(|m| m.contains("\"***\"")));
+
+    let mut command = Command::new("sh");
+    command.arg("-c").arg("exit 1 # ritual_test_secret_token");
+    let err = run_command(&mut command).unwrap_err().to_string();
+    assert!(!err.contains("ritual_test_secret_token"));
+    assert!(err.contains("***"));
+}
+
+#[cfg(unix)]
+#[test]
+fn run_command_limited_output() {
+    let _lock = lock_commands();
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg("i=0; while [ $i -lt 10000 ]; do echo 0123456789; i=$((i+1)); done; echo err >&2");
+    let output = run_command_and_capture_output_limited(&mut command, 100).unwrap();
+    assert!(output.is_success());
+    assert_eq!(
+        output.stdout,
+        format!(
+            "{}{}",
+            &"0123456789\n".repeat(10)[..100],
+            OUTPUT_TRUNCATED_MARKER
+        )
+    );
+    assert_eq!(output.stderr, "err\n");
+}
+
+#[test]
+fn remove_from_multihash_values() {
+    let mut hash: HashMap<&str, Vec<i32>> = HashMap::new();
+    add_to_multihash(&mut hash, "a", 1);
+    add_to_multihash(&mut hash, "a", 2);
+    add_to_multihash(&mut hash, "a", 3);
+    add_to_multihash(&mut hash, "b", 4);
+
+    assert!(remove_from_multihash(&mut hash, &"a", &2));
+    assert_eq!(hash["a"], vec![1, 3]);
+    assert!(!remove_from_multihash(&mut hash, &"a", &2));
+    assert!(!remove_from_multihash(&mut hash, &"c", &2));
+
+    assert!(remove_from_multihash(&mut hash, &"b", &4));
+    assert!(!hash.contains_key("b"));
+    assert_eq!(hash.len(), 1);
+
+    let mut hash: HashMap<&str, HashSet<i32>> = HashMap::new();
+    add_to_multihash(&mut hash, "a", 1);
+    assert!(remove_from_multihash(&mut hash, &"a", &1));
+    assert!(hash.is_empty());
+}
+
+#[test]
+fn add_to_multihash_set_deduplicates() {
+    let mut hash: HashMap<&str, HashSet<i32>> = HashMap::new();
+    add_to_multihash_set(&mut hash, "a", 1);
+    add_to_multihash_set(&mut hash, "a", 1);
+    assert_eq!(hash["a"].len(), 1);
+    add_to_multihash_set(&mut hash, "a", 2);
+    assert_eq!(hash["a"].len(), 2);
+
+    let mut hash: HashMap<&str, BTreeSet<i32>> = HashMap::new();
+    add_to_multihash_set(&mut hash, "a", 2);
+    add_to_multihash_set(&mut hash, "a", 1);
+    add_to_multihash_set(&mut hash, "a", 2);
+    assert_eq!(hash["a"].iter().cloned().collect::<Vec<_>>(), vec![1, 2]);
+}
+
+#[test]
+fn add_env_path_item_order() {
+    let name = "RITUAL_TEST_ENV_PATH_ORDER";
+    let existing = env::join_paths(vec![PathBuf::from("/a"), PathBuf::from("/b")]).unwrap();
+    env::set_var(name, &existing);
+    let new_paths = vec![
+        PathBuf::from("/b"),
+        PathBuf::from("/c"),
+        PathBuf::from("/c"),
+    ];
+
+    let value = add_env_path_item(name, new_paths.clone(), true).unwrap();
+    assert_eq!(
+        env::split_paths(&value).collect::<Vec<_>>(),
+        vec![
+            PathBuf::from("/b"),
+            PathBuf::from("/c"),
+            PathBuf::from("/a")
+        ]
+    );
+
+    let value = add_env_path_item(name, new_paths.clone(), false).unwrap();
+    assert_eq!(
+        env::split_paths(&value).collect::<Vec<_>>(),
+        vec![
+            PathBuf::from("/a"),
+            PathBuf::from("/b"),
+            PathBuf::from("/c")
+        ]
+    );
+
+    env::remove_var(name);
+    let value = add_env_path_item(name, new_paths, true).unwrap();
+    assert_eq!(
+        env::split_paths(&value).collect::<Vec<_>>(),
+        vec![PathBuf::from("/b"), PathBuf::from("/c")]
+    );
+}
+
+#[cfg(windows)]
+#[test]
+fn add_env_path_item_case_insensitive() {
+    let name = "RITUAL_TEST_ENV_PATH_CASE";
+    env::set_var(name, "C:\\Qt\\bin");
+    let value = add_env_path_item(name, vec![PathBuf::from("c:/qt/bin/")], true).unwrap();
+    assert_eq!(env::split_paths(&value).count(), 1);
+    env::remove_var(name);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn par_map_if_ok_sums_range() {
+    use crate::utils::ParMapIfOk;
+
+    let values: Result<Vec<u64>, String> = (0..1000_u64).par_map_if_ok(|x| Ok(x * 2));
+    let values = values.unwrap();
+    assert_eq!(values.len(), 1000);
+    assert_eq!(values[10], 20);
+    assert_eq!(values.iter().sum::<u64>(), 999 * 1000);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn par_map_if_ok_error() {
+    use crate::utils::ParMapIfOk;
+
+    let result = (0..1000_u64).par_map_if_ok(|x| {
+        if x == 500 {
+            Err(format!("bad value: {}", x))
+        } else {
+            Ok(x)
+        }
+    });
+    assert_eq!(result, Err("bad value: 500".to_string()));
+}
+
+#[test]
+fn inspect_log_passes_value_through() {
+    captured_log_messages("");
+
+    let value = vec![1, 2, 3].inspect_log(log::Level::Info, "inspect_log test:");
+    assert_eq!(value, vec![1, 2, 3]);
+    let value = Some("x").inspect("inspect test:");
+    assert_eq!(value, Some("x"));
+
+    assert_eq!(
+        captured_log_messages("inspect_log test:"),
+        vec!["inspect_log test: [1, 2, 3]".to_string()]
+    );
+    assert_eq!(
+        captured_log_messages("inspect test:"),
+        vec!["inspect test: Some(\"x\")".to_string()]
+    );
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+#[test]
+fn dylib_file_name_linux() {
+    assert_eq!(dylib_file_name("foo"), "libfoo.so");
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn dylib_file_name_macos() {
+    assert_eq!(dylib_file_name("foo"), "libfoo.dylib");
+}
+
+#[cfg(windows)]
+#[test]
+fn dylib_file_name_windows() {
+    assert_eq!(dylib_file_name("foo"), "foo.dll");
+}
+
+#[test]
+fn join_with_conjunction() {
+    let empty: Vec<&str> = Vec::new();
+    assert_eq!(empty.join_with_conjunction(", ", "or"), "");
+    assert_eq!(vec!["A"].join_with_conjunction(", ", "or"), "A");
+    assert_eq!(vec!["A", "B"].join_with_conjunction(", ", "or"), "A or B");
+    assert_eq!(
+        vec!["A", "B", "C"].join_with_conjunction(", ", "and"),
+        "A, B, and C"
+    );
+    assert_eq!((1..=3).join_with_conjunction("; ", "or"), "1; 2; or 3");
+}
+
+#[test]
+fn progress_bar_disabled() {
+    let _lock = lock_progress_bars();
+    ProgressBar::set_enabled(false);
+    let progress_bar = ProgressBar::new_in_memory(10, "test", false);
+    progress_bar.add(5);
+    progress_bar.finish();
+    let output = progress_bar.output();
+    ProgressBar::set_enabled(true);
+
+    assert_eq!(output, "");
+    assert!(progress_bar
+        .render()
+        .starts_with("test: 5 / 10 [00:00 elapsed"));
+}
+
+#[test]
+fn temp_dir() {
+    let temp_dir = TempDir::new("ritual_temp_dir_test").unwrap();
+    let path = temp_dir.path().to_path_buf();
+    assert!(path.is_dir());
+    assert!(path.starts_with(env::temp_dir()));
+    fs::write(path.join("file.txt"), "test").unwrap();
+    fs::create_dir(path.join("subdir")).unwrap();
+    fs::write(path.join("subdir").join("file.txt"), "test").unwrap();
+
+    let other = TempDir::new("ritual_temp_dir_test").unwrap();
+    assert_ne!(other.path(), path);
+
+    drop(temp_dir);
+    assert!(!path.exists());
+    assert!(other.path().is_dir());
+}
+
+#[test]
+fn temp_dir_keep() {
+    let mut temp_dir = TempDir::new("ritual_temp_dir_keep_test").unwrap();
+    temp_dir.set_keep(true);
+    let path = temp_dir.path().to_path_buf();
+    drop(temp_dir);
+    assert!(path.is_dir());
+    fs::remove_dir(path).unwrap();
+}
+
+#[test]
+fn hash_bytes_and_file() {
+    assert_eq!(hash_bytes(b""), "cbf29ce484222325");
+    assert_eq!(hash_bytes(b"a"), "af63dc4c8601ec8c");
+    assert_eq!(hash_bytes(b"foobar"), hash_bytes(b"foobar"));
+    assert_ne!(hash_bytes(b"foobar"), hash_bytes(b"foobaz"));
+    assert_ne!(hash_bytes(b"ab"), hash_bytes(b"ba"));
+
+    let temp_dir = TempDir::new("ritual_hash_file_test").unwrap();
+    let path1 = temp_dir.path().join("1.txt");
+    let path2 = temp_dir.path().join("2.txt");
+    fs::write(&path1, "foobar").unwrap();
+    fs::write(&path2, "foobar").unwrap();
+    assert_eq!(hash_file(&path1).unwrap(), hash_bytes(b"foobar"));
+    assert_eq!(hash_file(&path1).unwrap(), hash_file(&path2).unwrap());
+    fs::write(&path2, "foobaz").unwrap();
+    assert_ne!(hash_file(&path1).unwrap(), hash_file(&path2).unwrap());
+    assert!(hash_file(&temp_dir.path().join("missing.txt")).is_err());
+}
+
+#[test]
+fn write_if_changed_preserves_mtime() {
+    let temp_dir = TempDir::new("ritual_write_if_changed_test").unwrap();
+    let path = temp_dir.path().join("dir1").join("dir2").join("file.rs");
+    assert!(write_if_changed(&path, "fn main() {}").unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "fn main() {}");
+    let mtime = fs::metadata(&path).unwrap().modified().unwrap();
+
+    assert!(!write_if_changed(&path, "fn main() {}").unwrap());
+    assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), mtime);
+
+    assert!(write_if_changed(&path, "fn main() { }").unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "fn main() { }");
+}
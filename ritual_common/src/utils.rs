@@ -1,17 +1,21 @@
 //! Various utilities.
 
-use crate::errors::{bail, Result, ResultExt};
-use log::trace;
+use crate::errors::{bail, err_msg, Error, Result, ResultExt};
+use lazy_static::lazy_static;
+use log::{info, trace, warn};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::hash_map::{Entry, HashMap};
+use std::collections::{BTreeSet, HashSet};
 use std::ffi::OsString;
-use std::fmt::{Debug, Display};
+use std::fmt::{self, Debug, Display};
 use std::hash::{BuildHasher, Hash};
-use std::io::{stderr, stdout, Write};
-use std::path::PathBuf;
-use std::process::Command;
-use std::sync::{Arc, Mutex};
-use std::{env, iter, process};
+use std::io::{self, stderr, stdout, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use std::{env, iter, mem, process, thread};
 
 #[cfg(windows)]
 /// Returns proper executable file suffix on current platform.
@@ -27,6 +31,73 @@ pub fn exe_suffix() -> &'static str {
     ""
 }
 
+/// Returns the file name prefix of dynamic libraries on current platform.
+/// Returns `""` on Windows and `"lib"` on other platforms.
+pub fn dylib_prefix() -> &'static str {
+    if cfg!(windows) {
+        ""
+    } else {
+        "lib"
+    }
+}
+
+/// Returns the file name suffix of dynamic libraries on current platform.
+/// Returns `".dll"` on Windows, `".dylib"` on macOS and `".so"` on other platforms.
+pub fn dylib_suffix() -> &'static str {
+    if cfg!(windows) {
+        ".dll"
+    } else if cfg!(target_os = "macos") {
+        ".dylib"
+    } else {
+        ".so"
+    }
+}
+
+/// Returns the file name suffix of static libraries on current platform.
+/// Returns `".lib"` for MSVC targets and `".a"` on other platforms.
+pub fn staticlib_suffix() -> &'static str {
+    if cfg!(target_env = "msvc") {
+        ".lib"
+    } else {
+        ".a"
+    }
+}
+
+/// Returns the file name of a dynamic library with the name `stem`
+/// on current platform, e.g. `libfoo.so` for `foo` on Linux.
+pub fn dylib_file_name(stem: &str) -> String {
+    format!("{}{}{}", dylib_prefix(), stem, dylib_suffix())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Searches for executable `name` in directories listed in `PATH`
+/// and returns the full path to the first match.
+/// `exe_suffix()` is added to `name` if it doesn't have it.
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let file_name = if name.ends_with(exe_suffix()) {
+        name.to_string()
+    } else {
+        format!("{}{}", name, exe_suffix())
+    };
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| is_executable(path))
+}
+
 /// Creates and empty collection at `hash[key]` if there isn't one already.
 /// Adds `value` to `hash[key]` collection.
 pub fn add_to_multihash<K, T, V, S>(hash: &mut HashMap<K, V, S>, key: K, value: T)
@@ -45,16 +116,359 @@ where
     }
 }
 
-/// Runs a command and checks that it was successful
+/// A collection that stores each value at most once,
+/// so adding an existing value has no effect.
+pub trait SetCollection<T>: Default + Extend<T> {}
+
+impl<T: Eq + Hash, S: BuildHasher + Default> SetCollection<T> for HashSet<T, S> {}
+impl<T: Ord> SetCollection<T> for BTreeSet<T> {}
+
+/// Creates an empty set at `hash[key]` if there isn't one already.
+/// Adds `value` to `hash[key]` set unless it's already there.
+/// Insertion order of values is not preserved.
+pub fn add_to_multihash_set<K, T, V, S>(hash: &mut HashMap<K, V, S>, key: K, value: T)
+where
+    K: Eq + Hash + Clone,
+    V: SetCollection<T>,
+    S: BuildHasher,
+{
+    add_to_multihash(hash, key, value);
+}
+
+/// A collection that supports removing values.
+pub trait RemoveValue<T> {
+    /// Removes one occurrence of `value` from the collection.
+    /// Returns true if the value was present.
+    fn remove_value(&mut self, value: &T) -> bool;
+    /// Returns true if the collection contains no values.
+    fn is_empty(&self) -> bool;
+}
+
+impl<T: PartialEq> RemoveValue<T> for Vec<T> {
+    fn remove_value(&mut self, value: &T) -> bool {
+        if let Some(index) = self.iter().position(|x| x == value) {
+            self.remove(index);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        Vec::is_empty(self)
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> RemoveValue<T> for HashSet<T, S> {
+    fn remove_value(&mut self, value: &T) -> bool {
+        self.remove(value)
+    }
+
+    fn is_empty(&self) -> bool {
+        HashSet::is_empty(self)
+    }
+}
+
+impl<T: Ord> RemoveValue<T> for BTreeSet<T> {
+    fn remove_value(&mut self, value: &T) -> bool {
+        self.remove(value)
+    }
+
+    fn is_empty(&self) -> bool {
+        BTreeSet::is_empty(self)
+    }
+}
+
+/// Removes `value` from `hash[key]` collection. If the collection becomes empty,
+/// `key` is removed from `hash`. Returns true if the value was present.
+pub fn remove_from_multihash<K, T, V, S>(hash: &mut HashMap<K, V, S>, key: &K, value: &T) -> bool
+where
+    K: Eq + Hash,
+    V: RemoveValue<T>,
+    S: BuildHasher,
+{
+    let collection = match hash.get_mut(key) {
+        Some(collection) => collection,
+        None => return false,
+    };
+    let is_removed = collection.remove_value(value);
+    if collection.is_empty() {
+        hash.remove(key);
+    }
+    is_removed
+}
+
+/// Error returned when a command finishes with an unsuccessful status.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandFailed {
+    /// Debug representation of the command
+    pub command: String,
+    /// Exit status of the command
+    pub status: ExitStatus,
+}
+
+impl CommandFailed {
+    fn new(command: &Command, status: ExitStatus) -> Self {
+        CommandFailed {
+            command: command_text(command),
+            status,
+        }
+    }
+
+    /// Returns exit code of the command, or `None` if it was terminated by a signal.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.status.code()
+    }
+}
+
+impl fmt::Display for CommandFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "command failed with {}: {}", self.status, self.command)
+    }
+}
+
+impl std::error::Error for CommandFailed {}
+
+lazy_static! {
+    static ref SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+}
+
+/// Registers a string that should not appear in logs and error messages.
+/// Commands containing it (e.g. in an argument or environment variable)
+/// are logged with `***` in place of the secret. Arguments passed to
+/// the command are not affected.
+pub fn add_secret(secret: impl Into<String>) {
+    let secret = secret.into();
+    if !secret.is_empty() {
+        SECRETS.lock().unwrap().push(secret);
+    }
+}
+
+/// Replaces all registered secrets in `text` with `***`.
+pub fn redact_secrets(text: &str) -> String {
+    let mut text = text.to_string();
+    for secret in SECRETS.lock().unwrap().iter() {
+        text = text.replace(secret.as_str(), "***");
+    }
+    text
+}
+
+/// Returns representation of `command` suitable for logging.
+fn command_text(command: &Command) -> String {
+    redact_secrets(&format!("{:?}", command))
+}
+
+/// Returns name of the program executed by `command`.
+fn command_program(command: &Command) -> String {
+    // `Command` doesn't expose the program, but its debug representation
+    // starts with the quoted program name
+    let text = format!("{:?}", command);
+    let mut parts = text.splitn(3, '"');
+    match (parts.next(), parts.next()) {
+        (Some(""), Some(program)) => program.to_string(),
+        _ => text,
+    }
+}
+
+/// Converts an error that occurred while starting `command`.
+/// A missing executable results in an `io::ErrorKind::NotFound` error
+/// that doesn't mention the executable, so a clearer message is produced
+/// in this case.
+fn start_error(command: &Command, err: io::Error) -> Error {
+    let context = if err.kind() == io::ErrorKind::NotFound {
+        format!(
+            "executable `{}` not found in PATH; is it installed? (command: {})",
+            redact_secrets(&command_program(command)),
+            command_text(command)
+        )
+    } else {
+        format!("failed to run command: {}", command_text(command))
+    };
+    Error::from(err).context(context).into()
+}
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables dry run mode for the whole process. In dry run mode,
+/// functions of this module that run commands only log the command
+/// and report success (with empty output) without running it.
+pub fn set_dry_run(value: bool) {
+    DRY_RUN.store(value, Ordering::SeqCst);
+}
+
+/// Returns true if dry run mode is enabled.
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::SeqCst)
+}
+
+/// Returns true if dry run mode is enabled, in which case `command` is logged
+/// and must not be run.
+fn skip_in_dry_run(command: &Command) -> bool {
+    if is_dry_run() {
+        info!("Dry run: {}", command_text(command));
+        true
+    } else {
+        false
+    }
+}
+
+/// Returns output reported for commands skipped in dry run mode.
+fn dry_run_output() -> CommandOutput {
+    CommandOutput {
+        status: 0,
+        stdout: String::new(),
+        stderr: String::new(),
+    }
+}
+
+#[cfg(unix)]
+/// Returns exit status reported for commands skipped in dry run mode.
+fn dry_run_status() -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+
+    ExitStatus::from_raw(0)
+}
+
+#[cfg(windows)]
+/// Returns exit status reported for commands skipped in dry run mode.
+fn dry_run_status() -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+
+    ExitStatus::from_raw(0)
+}
+
+fn trace_command_finished(command: &Command, start_time: Instant, status: ExitStatus) {
+    trace!(
+        "Command finished in {:?} with {}: {}",
+        start_time.elapsed(),
+        status,
+        command_text(command)
+    );
+}
+
+/// Runs a command and checks that it was successful.
+/// On failure, the returned error can be downcasted to `CommandFailed`.
 pub fn run_command(command: &mut Command) -> Result<()> {
-    trace!("Executing command: {:?}", command);
-    let status = command
-        .status()
-        .with_context(|_| format!("failed to run command: {:?}", command))?;
+    if skip_in_dry_run(command) {
+        return Ok(());
+    }
+    trace!("Executing command: {}", command_text(command));
+    let start_time = Instant::now();
+    let status = command.status().map_err(|err| start_error(command, err))?;
+    trace_command_finished(command, start_time, status);
     if status.success() {
         Ok(())
     } else {
-        bail!("command failed with {}: {:?}", status, command);
+        Err(CommandFailed::new(command, status).into())
+    }
+}
+
+/// Maximal delay between attempts in `run_command_with_retries`.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Runs a command created by `command_factory` until it's successful, at most
+/// `attempts` times. The delay between attempts starts at `backoff` and is
+/// doubled after each failed attempt, up to `MAX_RETRY_DELAY`.
+/// Returns the last error if all attempts fail.
+pub fn run_command_with_retries(
+    command_factory: impl Fn() -> Command,
+    attempts: usize,
+    backoff: Duration,
+) -> Result<()> {
+    if attempts == 0 {
+        bail!("at least one attempt is required");
+    }
+    let mut delay = backoff;
+    for attempt in 1..=attempts {
+        match run_command(&mut command_factory()) {
+            Ok(()) => return Ok(()),
+            Err(err) => {
+                if attempt == attempts {
+                    return Err(err);
+                }
+                warn!(
+                    "Attempt {}/{} failed: {}; retrying in {:?}",
+                    attempt, attempts, err, delay
+                );
+                thread::sleep(delay);
+                delay = delay
+                    .checked_mul(2)
+                    .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY));
+            }
+        }
+    }
+    unreachable!()
+}
+
+/// Runs a command in `dir` and checks that it was successful.
+pub fn run_command_in_dir(command: &mut Command, dir: &Path) -> Result<()> {
+    trace!("Working directory: {}", dir.display());
+    command.current_dir(dir);
+    run_command(command)
+}
+
+/// Runs a command with additional environment variables `vars`
+/// and checks that it was successful.
+pub fn run_command_with_env(command: &mut Command, vars: &HashMap<String, String>) -> Result<()> {
+    for (name, value) in vars {
+        trace!(
+            "Environment variable: {}",
+            redact_secrets(&format!("{}={}", name, value))
+        );
+    }
+    command.envs(vars);
+    run_command(command)
+}
+
+/// Runs a command and checks that it was successful. If the command doesn't
+/// finish within `timeout`, it's killed and an error is returned.
+pub fn run_command_with_timeout(command: &mut Command, timeout: Duration) -> Result<()> {
+    if skip_in_dry_run(command) {
+        return Ok(());
+    }
+    trace!(
+        "Executing command with timeout {:?}: {}",
+        timeout,
+        command_text(command)
+    );
+    let mut child = command.spawn().map_err(|err| start_error(command, err))?;
+    let start_time = Instant::now();
+    loop {
+        let status = match child.try_wait() {
+            Ok(status) => status,
+            Err(err) => {
+                // don't leave the child running (or unreaped) if its status is unknown
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(Error::from(err)
+                    .context(format!(
+                        "failed to wait for command: {}",
+                        command_text(command)
+                    ))
+                    .into());
+            }
+        };
+        if let Some(status) = status {
+            if status.success() {
+                return Ok(());
+            } else {
+                return Err(CommandFailed::new(command, status).into());
+            }
+        }
+        if start_time.elapsed() >= timeout {
+            child
+                .kill()
+                .with_context(|_| format!("failed to kill command: {}", command_text(command)))?;
+            child.wait().with_context(|_| {
+                format!("failed to wait for command: {}", command_text(command))
+            })?;
+            bail!(
+                "command timed out after {:?}: {}",
+                timeout,
+                command_text(command)
+            );
+        }
+        thread::sleep(Duration::from_millis(10));
     }
 }
 
@@ -69,17 +483,269 @@ impl CommandOutput {
     pub fn is_success(&self) -> bool {
         self.status == 0
     }
+
+    /// Returns exit code of the command, or `None` if it was terminated by a signal.
+    pub fn exit_code(&self) -> Option<i32> {
+        if self.status == -1 {
+            None
+        } else {
+            Some(self.status)
+        }
+    }
+
+    /// Returns stdout without trailing whitespace.
+    pub fn stdout_trimmed(&self) -> &str {
+        self.stdout.trim_end()
+    }
+
+    /// Returns `self` if the command was successful.
+    /// Otherwise, returns an error containing the command's output.
+    pub fn into_result(self) -> Result<CommandOutput> {
+        if self.is_success() {
+            Ok(self)
+        } else {
+            bail!(
+                "command failed with status {}\nStdout:\n{}\nStderr:\n{}",
+                self.status,
+                self.stdout,
+                self.stderr
+            );
+        }
+    }
+
+    /// Returns stdout and stderr concatenated with a separator line.
+    /// If one of them is empty, the other one is returned as is.
+    pub fn combined(&self) -> String {
+        if self.stderr.is_empty() {
+            self.stdout.clone()
+        } else if self.stdout.is_empty() {
+            self.stderr.clone()
+        } else {
+            let separator = if self.stdout.ends_with('\n') {
+                ""
+            } else {
+                "\n"
+            };
+            format!(
+                "{}{}--- stderr ---\n{}",
+                self.stdout, separator, self.stderr
+            )
+        }
+    }
 }
 
 /// Runs a command and returns its output regardless of
 /// whether it was successful
 pub fn run_command_and_capture_output(command: &mut Command) -> Result<CommandOutput> {
-    trace!("Executing command: {:?}", command);
+    if skip_in_dry_run(command) {
+        return Ok(dry_run_output());
+    }
+    trace!("Executing command: {}", command_text(command));
+    let start_time = Instant::now();
+    command.stdout(process::Stdio::piped());
+    command.stderr(process::Stdio::piped());
+    let output = command.output().map_err(|err| start_error(command, err))?;
+    trace_command_finished(command, start_time, output.status);
+    Ok(CommandOutput {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        status: output.status.code().unwrap_or(-1),
+    })
+}
+
+/// Text appended to output that was truncated by
+/// `run_command_and_capture_output_limited`.
+pub const OUTPUT_TRUNCATED_MARKER: &str = "\n... (output truncated)\n";
+
+/// Reads all data from `reader` and returns at most `max_bytes` of it
+/// and whether there was more data.
+fn spawn_limited_reader<R: Read + Send + 'static>(
+    mut reader: R,
+    max_bytes: usize,
+) -> thread::JoinHandle<io::Result<(Vec<u8>, bool)>> {
+    thread::spawn(move || {
+        let mut data = Vec::new();
+        let mut is_truncated = false;
+        let mut buf = [0; 8192];
+        loop {
+            let count = match reader.read(&mut buf) {
+                Ok(0) => return Ok((data, is_truncated)),
+                Ok(count) => count,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            // the rest is discarded, but the pipe still needs to be drained
+            // to let the child exit
+            let accepted = count.min(max_bytes - data.len());
+            data.extend_from_slice(&buf[..accepted]);
+            if accepted < count {
+                is_truncated = true;
+            }
+        }
+    })
+}
+
+/// Runs a command and returns its output regardless of whether it was
+/// successful, keeping at most `max_bytes` of stdout and stderr each.
+/// If a stream is longer, it's truncated and `OUTPUT_TRUNCATED_MARKER` is appended to it.
+pub fn run_command_and_capture_output_limited(
+    command: &mut Command,
+    max_bytes: usize,
+) -> Result<CommandOutput> {
+    if skip_in_dry_run(command) {
+        return Ok(dry_run_output());
+    }
+    trace!("Executing command: {}", command_text(command));
+    command.stdout(process::Stdio::piped());
+    command.stderr(process::Stdio::piped());
+    let mut child = command.spawn().map_err(|err| start_error(command, err))?;
+    let stdout_thread =
+        spawn_limited_reader(child.stdout.take().expect("stdout is piped"), max_bytes);
+    let stderr_thread =
+        spawn_limited_reader(child.stderr.take().expect("stderr is piped"), max_bytes);
+
+    let mut streams = Vec::new();
+    for handle in vec![stdout_thread, stderr_thread] {
+        let (data, is_truncated) = handle
+            .join()
+            .map_err(|_| err_msg("output reader thread panicked"))?
+            .with_context(|_| {
+                format!(
+                    "failed to read output of command: {}",
+                    command_text(command)
+                )
+            })?;
+        let mut text = String::from_utf8_lossy(&data).to_string();
+        if is_truncated {
+            text.push_str(OUTPUT_TRUNCATED_MARKER);
+        }
+        streams.push(text);
+    }
+    let status = child
+        .wait()
+        .with_context(|_| format!("failed to wait for command: {}", command_text(command)))?;
+    let stderr = streams.pop().expect("stderr is missing");
+    let stdout = streams.pop().expect("stdout is missing");
+    Ok(CommandOutput {
+        stdout,
+        stderr,
+        status: status.code().unwrap_or(-1),
+    })
+}
+
+enum OutputLine {
+    Stdout(String),
+    Stderr(String),
+}
+
+/// Reads lines from `reader` and sends them to `sender` wrapped by `wrap`.
+fn spawn_line_reader<R: Read + Send + 'static>(
+    reader: R,
+    sender: mpsc::Sender<OutputLine>,
+    wrap: fn(String) -> OutputLine,
+) -> thread::JoinHandle<io::Result<()>> {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            if reader.read_until(b'\n', &mut buf)? == 0 {
+                return Ok(());
+            }
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.trim_end_matches('\n').trim_end_matches('\r');
+            // the receiver only disconnects if the main thread has panicked
+            let _ = sender.send(wrap(line.to_string()));
+        }
+    })
+}
+
+/// Runs a command and calls `on_stdout` and `on_stderr` for each line
+/// of its stdout and stderr as soon as it's received.
+/// Returns exit status of the command regardless of whether it was successful.
+pub fn run_command_streaming(
+    command: &mut Command,
+    mut on_stdout: impl FnMut(&str),
+    mut on_stderr: impl FnMut(&str),
+) -> Result<ExitStatus> {
+    if skip_in_dry_run(command) {
+        return Ok(dry_run_status());
+    }
+    trace!("Executing command: {}", command_text(command));
+    command.stdout(process::Stdio::piped());
+    command.stderr(process::Stdio::piped());
+    let mut child = command.spawn().map_err(|err| start_error(command, err))?;
+
+    // both pipes are read concurrently to avoid blocking the child
+    // when one of the pipe buffers is full
+    let (sender, receiver) = mpsc::channel();
+    let stdout_thread = spawn_line_reader(
+        child.stdout.take().expect("stdout is piped"),
+        sender.clone(),
+        OutputLine::Stdout,
+    );
+    let stderr_thread = spawn_line_reader(
+        child.stderr.take().expect("stderr is piped"),
+        sender,
+        OutputLine::Stderr,
+    );
+    for line in receiver {
+        match line {
+            OutputLine::Stdout(line) => on_stdout(&line),
+            OutputLine::Stderr(line) => on_stderr(&line),
+        }
+    }
+    for handle in vec![stdout_thread, stderr_thread] {
+        handle
+            .join()
+            .map_err(|_| err_msg("output reader thread panicked"))?
+            .with_context(|_| {
+                format!(
+                    "failed to read output of command: {}",
+                    command_text(command)
+                )
+            })?;
+    }
+    let status = child
+        .wait()
+        .with_context(|_| format!("failed to wait for command: {}", command_text(command)))?;
+    Ok(status)
+}
+
+/// Runs a command with `input` written to its stdin and returns its output
+/// regardless of whether it was successful.
+pub fn run_command_with_stdin(command: &mut Command, input: &[u8]) -> Result<CommandOutput> {
+    if skip_in_dry_run(command) {
+        return Ok(dry_run_output());
+    }
+    trace!("Executing command: {}", command_text(command));
+    command.stdin(process::Stdio::piped());
     command.stdout(process::Stdio::piped());
     command.stderr(process::Stdio::piped());
-    let output = command
-        .output()
-        .with_context(|_| format!("failed to run command: {:?}", command))?;
+    let mut child = command.spawn().map_err(|err| start_error(command, err))?;
+
+    // input is written on a separate thread to avoid a deadlock
+    // if the child fills its output pipe before reading all input
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_vec();
+    let writer_thread = thread::spawn(move || stdin.write_all(&input));
+
+    let output = child
+        .wait_with_output()
+        .with_context(|_| format!("failed to run command: {}", command_text(command)))?;
+    let write_result = writer_thread
+        .join()
+        .map_err(|_| err_msg("stdin writer thread panicked"))?;
+    if let Err(err) = write_result {
+        // the command is allowed to exit without reading all input
+        if err.kind() != io::ErrorKind::BrokenPipe {
+            bail!(
+                "failed to write stdin of command: {}: {}",
+                command_text(command),
+                err
+            );
+        }
+    }
     Ok(CommandOutput {
         stdout: String::from_utf8_lossy(&output.stdout).to_string(),
         stderr: String::from_utf8_lossy(&output.stderr).to_string(),
@@ -87,14 +753,59 @@ pub fn run_command_and_capture_output(command: &mut Command) -> Result<CommandOu
     })
 }
 
+/// Runs a command and returns its output regardless of whether it was successful.
+/// Stderr of the command is redirected to its stdout, so `stdout` of the result
+/// contains both streams in the original order and `stderr` is empty.
+///
+/// Note that this function overrides stdout and stderr of `command`. After it returns,
+/// both are set to `Stdio::null()`, so they should be configured again if `command`
+/// is reused.
+pub fn run_command_and_capture_combined(command: &mut Command) -> Result<CommandOutput> {
+    if skip_in_dry_run(command) {
+        return Ok(dry_run_output());
+    }
+    trace!("Executing command: {}", command_text(command));
+    let (mut reader, writer) = os_pipe::pipe().with_context(|_| "failed to create pipe")?;
+    let writer_clone = writer
+        .try_clone()
+        .with_context(|_| "failed to clone pipe")?;
+    command.stdout(writer);
+    command.stderr(writer_clone);
+    let spawn_result = command.spawn();
+    // `command` holds the write ends of the pipe; they must be closed
+    // for the read to end
+    command.stdout(process::Stdio::null());
+    command.stderr(process::Stdio::null());
+    let mut child = spawn_result.map_err(|err| start_error(command, err))?;
+
+    let mut output = Vec::new();
+    reader.read_to_end(&mut output).with_context(|_| {
+        format!(
+            "failed to read output of command: {}",
+            command_text(command)
+        )
+    })?;
+    let status = child
+        .wait()
+        .with_context(|_| format!("failed to wait for command: {}", command_text(command)))?;
+    Ok(CommandOutput {
+        stdout: String::from_utf8_lossy(&output).to_string(),
+        stderr: String::new(),
+        status: status.code().unwrap_or(-1),
+    })
+}
+
 /// Runs a command and returns its stdout if it was successful
 pub fn get_command_output(command: &mut Command) -> Result<String> {
-    trace!("Executing command: {:?}", command);
+    if skip_in_dry_run(command) {
+        return Ok(String::new());
+    }
+    trace!("Executing command: {}", command_text(command));
+    let start_time = Instant::now();
     command.stdout(process::Stdio::piped());
     command.stderr(process::Stdio::piped());
-    let output = command
-        .output()
-        .with_context(|_| format!("failed to run command: {:?}", command))?;
+    let output = command.output().map_err(|err| start_error(command, err))?;
+    trace_command_finished(command, start_time, output.status);
     if output.status.success() {
         Ok(String::from_utf8(output.stdout)
             .with_context(|_| "comand output is not valid unicode")?)
@@ -108,8 +819,28 @@ pub fn get_command_output(command: &mut Command) -> Result<String> {
         stderr
             .write_all(&output.stderr)
             .with_context(|_| "output failed")?;
-        bail!("command failed with {}: {:?}", output.status, command);
+        Err(CommandFailed::new(command, output.status).into())
+    }
+}
+
+/// Runs a command and returns its exit status, stdout and stderr.
+/// Unlike `get_command_output`, a nonzero exit code is not treated as an error,
+/// and nothing is written to stderr.
+pub fn get_command_output_allow_failure(command: &mut Command) -> Result<CommandOutput> {
+    if skip_in_dry_run(command) {
+        return Ok(dry_run_output());
     }
+    trace!("Executing command: {}", command_text(command));
+    let start_time = Instant::now();
+    command.stdout(process::Stdio::piped());
+    command.stderr(process::Stdio::piped());
+    let output = command.output().map_err(|err| start_error(command, err))?;
+    trace_command_finished(command, start_time, output.status);
+    Ok(CommandOutput {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        status: output.status.code().unwrap_or(-1),
+    })
 }
 
 /// Perform a map operation that can fail
@@ -132,75 +863,839 @@ impl<A, T: IntoIterator<Item = A>> MapIfOk<A> for T {
     }
 }
 
+/// Perform a parallel map operation that can fail
+#[cfg(feature = "rayon")]
+pub trait ParMapIfOk<A> {
+    /// Call closure `f` on each element of the collection in parallel and return
+    /// `Vec` of values returned by the closure in the original order.
+    /// If closure returns `Err` for any element, return one of the errors instead.
+    fn par_map_if_ok<B, E, F>(self, f: F) -> std::result::Result<Vec<B>, E>
+    where
+        B: Send,
+        E: Send,
+        F: Fn(A) -> std::result::Result<B, E> + Sync + Send;
+}
+
+#[cfg(feature = "rayon")]
+impl<A: Send, T: rayon::iter::IntoParallelIterator<Item = A>> ParMapIfOk<A> for T {
+    fn par_map_if_ok<B, E, F>(self, f: F) -> std::result::Result<Vec<B>, E>
+    where
+        B: Send,
+        E: Send,
+        F: Fn(A) -> std::result::Result<B, E> + Sync + Send,
+    {
+        use rayon::iter::ParallelIterator;
+
+        self.into_par_iter().map(f).collect()
+    }
+}
+
+#[cfg(windows)]
+/// Returns true if `a` and `b` refer to the same directory in a path list.
+/// Comparison is case-insensitive and ignores the kind of separators.
+fn is_same_env_path(a: &Path, b: &Path) -> bool {
+    let normalize = |path: &Path| {
+        path.to_string_lossy()
+            .replace('/', "\\")
+            .trim_end_matches('\\')
+            .to_lowercase()
+    };
+    normalize(a) == normalize(b)
+}
+
+#[cfg(not(windows))]
+/// Returns true if `a` and `b` refer to the same directory in a path list.
+fn is_same_env_path(a: &Path, b: &Path) -> bool {
+    a == b
+}
+
+/// Returns `paths` without duplicates, keeping the first occurrence of each path.
+fn deduplicate_env_paths(paths: impl IntoIterator<Item = PathBuf>) -> Vec<PathBuf> {
+    let mut result: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        if !result.iter().any(|x| is_same_env_path(x, &path)) {
+            result.push(path);
+        }
+    }
+    result
+}
+
 /// Reads environment variable `env_var_name`, adds `new_paths`
 /// to acquired list of paths and returns the list formatted as path list
 /// (without applying it).
-pub fn add_env_path_item(env_var_name: &str, mut new_paths: Vec<PathBuf>) -> Result<OsString> {
-    for path in env::split_paths(&env::var(env_var_name).unwrap_or_default()) {
-        if new_paths.iter().find(|&x| x == &path).is_none() {
-            new_paths.push(path);
-        }
-    }
-    Ok(env::join_paths(new_paths).with_context(|_| "env::join_paths failed")?)
+///
+/// If `prepend` is true, `new_paths` are placed before the existing paths,
+/// so they take priority. Otherwise, they are placed after the existing paths.
+/// Duplicates are removed, keeping the first occurrence.
+pub fn add_env_path_item(
+    env_var_name: &str,
+    new_paths: Vec<PathBuf>,
+    prepend: bool,
+) -> Result<OsString> {
+    let existing_paths = env::var_os(env_var_name).unwrap_or_default();
+    let existing_paths = env::split_paths(&existing_paths).filter(|p| !p.as_os_str().is_empty());
+    let paths = if prepend {
+        deduplicate_env_paths(new_paths.into_iter().chain(existing_paths))
+    } else {
+        deduplicate_env_paths(existing_paths.chain(new_paths))
+    };
+    Ok(env::join_paths(paths).with_context(|_| "env::join_paths failed")?)
 }
 
-pub trait Inspect {
-    fn inspect(self, text: impl Display) -> Self;
+pub trait Inspect: Sized {
+    /// Logs `text` and the debug representation of the value at `Debug` level
+    /// and returns the value unchanged.
+    fn inspect(self, text: impl Display) -> Self {
+        self.inspect_log(log::Level::Debug, text)
+    }
+
+    /// Logs `text` and the debug representation of the value at the specified `level`
+    /// and returns the value unchanged.
+    fn inspect_log(self, level: log::Level, text: impl Display) -> Self;
 }
 
 impl<T: Debug> Inspect for T {
-    fn inspect(self, text: impl Display) -> Self {
-        println!("{} {:?}", text, self);
+    fn inspect_log(self, level: log::Level, text: impl Display) -> Self {
+        log::log!(level, "{} {:?}", text, self);
         self
     }
 }
 
+/// Returns a hex digest of `data` that is stable across runs and platforms
+/// (64-bit FNV-1a hash). Intended for detecting changes of generated files,
+/// not for cryptographic purposes.
+pub fn hash_bytes(data: &[u8]) -> String {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+    let hash = data.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    });
+    format!("{:016x}", hash)
+}
+
+/// A directory under the system temporary directory that is removed
+/// (recursively) when the guard is dropped. Unlike `tempdir::TempDir`,
+/// the directory can be kept for debugging, and cleanup failures are logged.
+#[derive(Debug)]
+pub struct TempDir {
+    dir: Option<tempdir::TempDir>,
+    keep: bool,
+}
+
+impl TempDir {
+    /// Creates a new uniquely named directory. Its name starts with `prefix`.
+    pub fn new(prefix: &str) -> Result<Self> {
+        let dir = tempdir::TempDir::new(prefix)
+            .with_context(|_| format!("Failed to create temporary directory: {}", prefix))?;
+        trace!("Created temporary directory: {}", dir.path().display());
+        Ok(TempDir {
+            dir: Some(dir),
+            keep: false,
+        })
+    }
+
+    /// Returns path to the directory.
+    pub fn path(&self) -> &Path {
+        self.dir.as_ref().expect("dir is only taken on drop").path()
+    }
+
+    /// If `keep` is true, the directory is not removed on drop
+    /// (e.g. to inspect its content for debugging).
+    pub fn set_keep(&mut self, keep: bool) {
+        self.keep = keep;
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let dir = match self.dir.take() {
+            Some(dir) => dir,
+            None => return,
+        };
+        if self.keep {
+            let path = dir.into_path();
+            info!("Keeping temporary directory: {}", path.display());
+            return;
+        }
+        let path = dir.path().to_path_buf();
+        if let Err(err) = dir.close() {
+            warn!(
+                "Failed to clean up temporary directory {}: {}",
+                path.display(),
+                err
+            );
+        }
+    }
+}
+
+/// Destination of progress bar output.
+#[derive(Debug)]
+enum ProgressBarOutput {
+    Stdout,
+    Buffer(String),
+    Log,
+}
+
+impl ProgressBarOutput {
+    fn write(&mut self, text: &str) {
+        if !ProgressBar::is_enabled() {
+            return;
+        }
+        match self {
+            ProgressBarOutput::Stdout => {
+                // progress bar is cosmetic, so failing to write it (e.g. to a closed pipe)
+                // shouldn't abort the program
+                let _ = write!(stdout(), "{}", text);
+                let _ = stdout().flush();
+            }
+            ProgressBarOutput::Buffer(buffer) => buffer.push_str(text),
+            ProgressBarOutput::Log => info!("{}", text.trim_end()),
+        }
+    }
+}
+
+/// Output shared by several progress bars displayed on separate lines.
+#[derive(Debug)]
+struct ProgressDisplay {
+    output: ProgressBarOutput,
+    /// If true, all lines are redrawn in place using cursor movement.
+    /// Otherwise, the progress bars print plain lines as usual.
+    is_terminal: bool,
+    /// Ids and current texts of displayed lines.
+    lines: Vec<(u64, String)>,
+    /// Number of lines currently drawn in the terminal above the cursor.
+    drawn_lines: usize,
+    next_line_id: u64,
+}
+
+impl ProgressDisplay {
+    fn new(output: ProgressBarOutput, is_terminal: bool) -> Self {
+        ProgressDisplay {
+            output,
+            is_terminal,
+            lines: Vec::new(),
+            drawn_lines: 0,
+            next_line_id: 0,
+        }
+    }
+
+    /// Adds a line to the bottom of the display and returns its id.
+    fn add_line(&mut self, text: String) -> u64 {
+        let id = self.next_line_id;
+        self.next_line_id += 1;
+        self.lines.push((id, text));
+        id
+    }
+
+    fn set_line(&mut self, id: u64, text: String) {
+        if let Some(line) = self.lines.iter_mut().find(|(line_id, _)| *line_id == id) {
+            line.1 = text;
+        }
+        self.redraw(None);
+    }
+
+    /// Removes a line and prints `permanent_text` above the remaining lines.
+    fn remove_line(&mut self, id: u64, permanent_text: Option<&str>) {
+        self.lines.retain(|(line_id, _)| *line_id != id);
+        self.redraw(permanent_text);
+    }
+
+    /// Writes text that is not a part of any line.
+    fn write(&mut self, text: &str) {
+        self.output.write(text);
+    }
+
+    fn redraw(&mut self, permanent_text: Option<&str>) {
+        if !self.is_terminal {
+            if let Some(permanent_text) = permanent_text {
+                self.output.write(permanent_text);
+            }
+            return;
+        }
+        let mut text = String::new();
+        if self.drawn_lines > 0 {
+            // move to the first drawn line
+            text.push_str(&format!("\x1b[{}A", self.drawn_lines));
+        }
+        if let Some(permanent_text) = permanent_text {
+            text.push_str("\r\x1b[2K");
+            text.push_str(permanent_text);
+        }
+        for (_, line) in &self.lines {
+            text.push_str(&format!("\r\x1b[2K{}\n", line));
+        }
+        // clear lines left from the previous draw
+        text.push_str("\x1b[J");
+        self.drawn_lines = self.lines.len();
+        self.output.write(&text);
+    }
+}
+
+/// Where a progress bar writes its output.
+#[derive(Debug)]
+enum ProgressBarTarget {
+    Own(ProgressBarOutput),
+    Shared {
+        display: Arc<Mutex<ProgressDisplay>>,
+        line_id: u64,
+    },
+}
+
 #[derive(Debug)]
 struct ProgressBarInner {
     message: String,
     count: u64,
     pos: u64,
     last_line_len: usize,
+    start_time: Instant,
+    /// If true, the line is redrawn in place using `\r`. Otherwise,
+    /// a plain line is printed on every 10% of progress.
+    is_terminal: bool,
+    /// Last printed progress (in tens of percent) in non-terminal mode.
+    last_printed_step: Option<u64>,
+    target: ProgressBarTarget,
+    /// Parent of a child progress bar created with `ProgressBar::add_child`.
+    parent: Option<ProgressBar>,
+    is_finished: bool,
+    /// Time of the last redraw in terminal mode.
+    last_draw_time: Option<Instant>,
+    /// Minimal time between redraws in terminal mode.
+    redraw_interval: Duration,
+    /// Terminal width used for displaying the visual bar.
+    /// If `None`, only counters are displayed.
+    bar_columns: Option<usize>,
+    /// If true, the total count is unknown, and a spinner is displayed
+    /// instead of the total count and the estimated time.
+    is_spinner: bool,
+    /// Index of the current frame in `SPINNER_FRAMES`.
+    spinner_frame: usize,
+}
+
+/// Locks `mutex` even if it's poisoned. Progress output is not critical,
+/// and finishing a progress bar (possibly in `Drop`) must not panic.
+fn lock_ignoring_poison<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Maximal width of the visual bar (in characters).
+const MAX_PROGRESS_BAR_WIDTH: usize = 50;
+
+/// Animation frames of the spinner for progress bars with unknown count.
+const SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+
+/// Formats `seconds` as `mm:ss` or `h:mm:ss`.
+fn format_duration(seconds: u64) -> String {
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+lazy_static! {
+    static ref PROGRESS_BARS_ENABLED: AtomicBool =
+        AtomicBool::new(env::var_os("RITUAL_NO_PROGRESS").is_none());
 }
 
 #[derive(Clone, Debug)]
 pub struct ProgressBar(Arc<Mutex<ProgressBarInner>>);
 
 impl ProgressBar {
+    /// Enables or disables output of all progress bars in the process.
+    /// Progress bars are enabled by default unless `RITUAL_NO_PROGRESS`
+    /// environment variable is set. Disabled progress bars don't write anything,
+    /// but their state is still updated.
+    pub fn set_enabled(enabled: bool) {
+        PROGRESS_BARS_ENABLED.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Returns true if progress bars output is enabled.
+    pub fn is_enabled() -> bool {
+        PROGRESS_BARS_ENABLED.load(Ordering::SeqCst)
+    }
+
+    /// Creates a progress bar printing to stdout. If stdout is not a terminal,
+    /// the progress is printed as plain lines on every 10% instead of
+    /// redrawing the line.
     pub fn new(count: u64, message: impl Into<String>) -> Self {
-        let mut progress_bar = ProgressBarInner {
+        ProgressBar::with_output(
             count,
-            message: message.into(),
-            pos: 0,
-            last_line_len: 0,
+            message.into(),
+            atty::is(atty::Stream::Stdout),
+            ProgressBarOutput::Stdout,
+        )
+    }
+
+    /// Creates a progress bar that reports the progress using `log::info!`
+    /// on every 10% instead of printing to stdout.
+    pub fn new_with_log(count: u64, message: impl Into<String>) -> Self {
+        ProgressBar::with_output(count, message.into(), false, ProgressBarOutput::Log)
+    }
+
+    /// Creates a progress bar that writes to an in-memory buffer instead of stdout,
+    /// using terminal or non-terminal mode as specified by `is_terminal`.
+    /// The written text can be retrieved with `output()`.
+    pub fn new_in_memory(count: u64, message: impl Into<String>, is_terminal: bool) -> Self {
+        ProgressBar::with_output(
+            count,
+            message.into(),
+            is_terminal,
+            ProgressBarOutput::Buffer(String::new()),
+        )
+    }
+
+    /// Creates a spinner printing to stdout for a process with unknown number of steps.
+    /// Call `tick()` to advance the animation. If stdout is not a terminal,
+    /// only the initial and the final lines are printed.
+    pub fn new_spinner(message: impl Into<String>) -> Self {
+        let mut inner = ProgressBarInner::new(
+            0,
+            message.into(),
+            atty::is(atty::Stream::Stdout),
+            ProgressBarTarget::Own(ProgressBarOutput::Stdout),
+        );
+        inner.is_spinner = true;
+        ProgressBar::from_inner(inner)
+    }
+
+    /// Creates a spinner that writes to an in-memory buffer instead of stdout,
+    /// using terminal or non-terminal mode as specified by `is_terminal`.
+    pub fn new_spinner_in_memory(message: impl Into<String>, is_terminal: bool) -> Self {
+        let mut inner = ProgressBarInner::new(
+            0,
+            message.into(),
+            is_terminal,
+            ProgressBarTarget::Own(ProgressBarOutput::Buffer(String::new())),
+        );
+        inner.is_spinner = true;
+        ProgressBar::from_inner(inner)
+    }
+
+    fn with_output(
+        count: u64,
+        message: String,
+        is_terminal: bool,
+        output: ProgressBarOutput,
+    ) -> Self {
+        ProgressBar::from_inner(ProgressBarInner::new(
+            count,
+            message,
+            is_terminal,
+            ProgressBarTarget::Own(output),
+        ))
+    }
+
+    fn from_inner(mut inner: ProgressBarInner) -> Self {
+        inner.print();
+        ProgressBar(Arc::new(Mutex::new(inner)))
+    }
+
+    /// Creates a child progress bar displayed on a separate line below this one.
+    /// When the child is finished, its line is removed,
+    /// and the position of this progress bar is advanced by 1.
+    pub fn add_child(&self, count: u64, message: impl Into<String>) -> ProgressBar {
+        let (display, is_terminal, redraw_interval, bar_columns) = {
+            let mut inner = self.0.lock().unwrap();
+            (
+                inner.shared_display(),
+                inner.is_terminal,
+                inner.redraw_interval,
+                inner.bar_columns,
+            )
         };
-        progress_bar.print();
-        ProgressBar(Arc::new(Mutex::new(progress_bar)))
+        let line_id = display.lock().unwrap().add_line(String::new());
+        let mut child = ProgressBarInner::new(
+            count,
+            message.into(),
+            is_terminal,
+            ProgressBarTarget::Shared { display, line_id },
+        );
+        child.redraw_interval = redraw_interval;
+        child.bar_columns = bar_columns;
+        child.parent = Some(self.clone());
+        ProgressBar::from_inner(child)
     }
 
     pub fn add(&self, n: u64) {
         self.0.lock().unwrap().inc(n);
     }
+
+    /// Advances the animation of a spinner created with `new_spinner`.
+    pub fn tick(&self) {
+        let mut inner = self.0.lock().unwrap();
+        inner.spinner_frame = (inner.spinner_frame + 1) % SPINNER_FRAMES.len();
+        inner.print();
+    }
+
+    /// Replaces the message displayed before the counters.
+    pub fn set_message(&self, message: impl Into<String>) {
+        let mut inner = self.0.lock().unwrap();
+        inner.message = message.into();
+        inner.print();
+    }
+
+    /// Sets the current position. Values greater than the total count are clamped.
+    pub fn set_position(&self, pos: u64) {
+        let mut inner = self.0.lock().unwrap();
+        inner.pos = pos.min(inner.count);
+        inner.print();
+    }
+
+    /// Sets minimal time between redraws in terminal mode (50 ms by default).
+    /// Updates that happen sooner are not printed immediately.
+    pub fn set_redraw_interval(&self, interval: Duration) {
+        self.0.lock().unwrap().redraw_interval = interval;
+    }
+
+    /// Enables displaying a visual bar (`[####----] 50%`) sized to the width
+    /// of the terminal. If the width is unknown (e.g. if stdout is not a terminal),
+    /// only counters are displayed.
+    pub fn enable_bar(&self) {
+        let columns = terminal_size::terminal_size().map(|(width, _)| width.0 as usize);
+        self.set_bar_columns(columns);
+    }
+
+    /// Enables displaying a visual bar assuming that the terminal is `columns` wide.
+    /// If `columns` is `None`, the visual bar is disabled.
+    pub fn set_bar_columns(&self, columns: Option<usize>) {
+        self.0.lock().unwrap().bar_columns = columns;
+    }
+
+    /// Prints the final state of the progress bar with the total elapsed time
+    /// and ends the line. Further updates are not printed.
+    /// This is done automatically when the last clone of the progress bar is dropped.
+    pub fn finish(&self) {
+        self.0.lock().unwrap().finish();
+    }
+
+    /// Returns the current progress line, e.g.
+    /// `message: 5 / 10 [00:12 elapsed, ~00:12 left]`
+    /// (or `message: | 5 [00:12 elapsed]` for a spinner).
+    pub fn render(&self) -> String {
+        self.0.lock().unwrap().render()
+    }
+
+    /// Returns all text written by a progress bar created with `new_in_memory`
+    /// (including its child progress bars).
+    /// Returns an empty string for progress bars printing to stdout or log.
+    pub fn output(&self) -> String {
+        match &self.0.lock().unwrap().target {
+            ProgressBarTarget::Own(output) => output_buffer(output),
+            ProgressBarTarget::Shared { display, .. } => {
+                output_buffer(&display.lock().unwrap().output)
+            }
+        }
+    }
+
+    /// Returns the lines currently displayed by this progress bar
+    /// and other progress bars sharing its lines (e.g. its children).
+    pub fn lines(&self) -> Vec<String> {
+        let inner = self.0.lock().unwrap();
+        let lines = match &inner.target {
+            ProgressBarTarget::Own(_) => {
+                if inner.is_finished {
+                    Vec::new()
+                } else {
+                    vec![inner.render()]
+                }
+            }
+            ProgressBarTarget::Shared { display, .. } => display
+                .lock()
+                .unwrap()
+                .lines
+                .iter()
+                .map(|(_, line)| line.clone())
+                .collect(),
+        };
+        lines
+    }
+}
+
+/// Manager of several progress bars displayed on separate lines,
+/// e.g. one per worker thread. Draws of all its progress bars are serialized,
+/// and all active bars are repainted together, so their output doesn't interleave.
+#[derive(Clone, Debug)]
+pub struct MultiProgress(Arc<Mutex<ProgressDisplay>>);
+
+impl MultiProgress {
+    /// Creates a manager printing to stdout. If stdout is not a terminal,
+    /// each progress bar prints plain lines as usual.
+    pub fn new() -> Self {
+        MultiProgress::with_output(atty::is(atty::Stream::Stdout), ProgressBarOutput::Stdout)
+    }
+
+    /// Creates a manager that writes to an in-memory buffer instead of stdout,
+    /// using terminal or non-terminal mode as specified by `is_terminal`.
+    /// The written text can be retrieved with `output()`.
+    pub fn new_in_memory(is_terminal: bool) -> Self {
+        MultiProgress::with_output(is_terminal, ProgressBarOutput::Buffer(String::new()))
+    }
+
+    fn with_output(is_terminal: bool, output: ProgressBarOutput) -> Self {
+        MultiProgress(Arc::new(Mutex::new(ProgressDisplay::new(
+            output,
+            is_terminal,
+        ))))
+    }
+
+    /// Creates a progress bar displayed on a new line below the existing ones.
+    /// When the progress bar is finished, its final state is printed
+    /// above the active progress bars.
+    pub fn add(&self, count: u64, message: impl Into<String>) -> ProgressBar {
+        let (line_id, is_terminal) = {
+            let mut display = self.0.lock().unwrap();
+            (display.add_line(String::new()), display.is_terminal)
+        };
+        ProgressBar::from_inner(ProgressBarInner::new(
+            count,
+            message.into(),
+            is_terminal,
+            ProgressBarTarget::Shared {
+                display: Arc::clone(&self.0),
+                line_id,
+            },
+        ))
+    }
+
+    /// Returns the lines of all active progress bars.
+    pub fn lines(&self) -> Vec<String> {
+        self.0
+            .lock()
+            .unwrap()
+            .lines
+            .iter()
+            .map(|(_, line)| line.clone())
+            .collect()
+    }
+
+    /// Returns the lines of all active progress bars joined with newlines.
+    pub fn render(&self) -> String {
+        self.lines().join("\n")
+    }
+
+    /// Returns all text written by a manager created with `new_in_memory`.
+    /// Returns an empty string for a manager printing to stdout.
+    pub fn output(&self) -> String {
+        output_buffer(&self.0.lock().unwrap().output)
+    }
+}
+
+impl Default for MultiProgress {
+    fn default() -> Self {
+        MultiProgress::new()
+    }
+}
+
+fn output_buffer(output: &ProgressBarOutput) -> String {
+    match output {
+        ProgressBarOutput::Stdout | ProgressBarOutput::Log => String::new(),
+        ProgressBarOutput::Buffer(buffer) => buffer.clone(),
+    }
 }
 
 impl ProgressBarInner {
-    fn clear_line(&self) {
-        print!("\r");
-        for _ in 0..self.last_line_len {
-            print!(" ");
+    fn new(count: u64, message: String, is_terminal: bool, target: ProgressBarTarget) -> Self {
+        ProgressBarInner {
+            count,
+            message,
+            pos: 0,
+            last_line_len: 0,
+            start_time: Instant::now(),
+            is_terminal,
+            last_printed_step: None,
+            target,
+            parent: None,
+            is_finished: false,
+            last_draw_time: None,
+            redraw_interval: Duration::from_millis(50),
+            bar_columns: None,
+            is_spinner: false,
+            spinner_frame: 0,
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        match &mut self.target {
+            ProgressBarTarget::Own(output) => output.write(text),
+            ProgressBarTarget::Shared { display, .. } => lock_ignoring_poison(display).write(text),
+        }
+    }
+
+    /// Returns the display shared with child progress bars,
+    /// moving the output of this progress bar to it if necessary.
+    fn shared_display(&mut self) -> Arc<Mutex<ProgressDisplay>> {
+        if let ProgressBarTarget::Shared { display, .. } = &self.target {
+            return Arc::clone(display);
+        }
+        if self.is_terminal {
+            self.clear_line();
         }
-        print!("\r");
+        let old_target = mem::replace(
+            &mut self.target,
+            ProgressBarTarget::Own(ProgressBarOutput::Log),
+        );
+        let output = match old_target {
+            ProgressBarTarget::Own(output) => output,
+            ProgressBarTarget::Shared { .. } => unreachable!(),
+        };
+        let mut display = ProgressDisplay::new(output, self.is_terminal);
+        let line_id = display.add_line(self.render());
+        let display = Arc::new(Mutex::new(display));
+        self.target = ProgressBarTarget::Shared {
+            display: Arc::clone(&display),
+            line_id,
+        };
+        self.last_draw_time = None;
+        self.print();
+        display
+    }
+
+    fn clear_line(&mut self) {
+        let text = format!("\r{}\r", " ".repeat(self.last_line_len));
+        self.write(&text);
+    }
+
+    fn render(&self) -> String {
+        let elapsed = self.start_time.elapsed();
+        if self.is_spinner {
+            let pos_text = if self.pos > 0 {
+                format!(" {}", self.pos)
+            } else {
+                String::new()
+            };
+            return format!(
+                "{}: {}{} [{} elapsed]",
+                self.message,
+                SPINNER_FRAMES[self.spinner_frame],
+                pos_text,
+                format_duration(elapsed.as_secs())
+            );
+        }
+        let mut counters = format!(
+            "{} / {} [{} elapsed",
+            self.pos,
+            self.count,
+            format_duration(elapsed.as_secs())
+        );
+        // ETA is based on the average rate since the start
+        if self.pos > 0 && self.pos < self.count {
+            let left = elapsed.as_secs_f64() * (self.count - self.pos) as f64 / self.pos as f64;
+            counters.push_str(&format!(", ~{} left", format_duration(left as u64)));
+        }
+        counters.push(']');
+
+        if let Some(columns) = self.bar_columns {
+            let percent = if self.count == 0 {
+                100
+            } else {
+                self.pos.min(self.count) * 100 / self.count
+            };
+            let counters = format!("{}% {}", percent, counters);
+            // `message: [bar] counters`, leaving the last column empty
+            // to avoid wrapping
+            let bar_width = columns
+                .saturating_sub(self.message.len() + counters.len() + 6)
+                .min(MAX_PROGRESS_BAR_WIDTH);
+            if bar_width > 0 {
+                let filled = (bar_width as u64 * percent / 100) as usize;
+                return format!(
+                    "{}: [{}{}] {}",
+                    self.message,
+                    "#".repeat(filled),
+                    "-".repeat(bar_width - filled),
+                    counters
+                );
+            }
+        }
+        format!("{}: {}", self.message, counters)
     }
+
     fn print(&mut self) {
-        self.clear_line();
-        let message = format!("{}: {} / {}", self.message, self.pos, self.count);
-        self.last_line_len = message.len();
-        print!("{}\r", message);
-        stdout().flush().unwrap();
+        if self.is_finished {
+            return;
+        }
+        let message = self.render();
+        if self.is_terminal {
+            if let Some(last_draw_time) = self.last_draw_time {
+                if last_draw_time.elapsed() < self.redraw_interval {
+                    return;
+                }
+            }
+            self.last_draw_time = Some(Instant::now());
+            if let ProgressBarTarget::Shared { display, line_id } = &self.target {
+                lock_ignoring_poison(display).set_line(*line_id, message);
+                return;
+            }
+            self.clear_line();
+            self.last_line_len = message.len();
+            self.write(&format!("{}\r", message));
+        } else if self.is_spinner {
+            // the number of steps is unknown, so only the first line is printed
+            if self.last_printed_step.is_none() {
+                self.last_printed_step = Some(0);
+                self.write(&format!("{}\n", message));
+            }
+        } else {
+            let step = if self.count == 0 {
+                10
+            } else {
+                self.pos.min(self.count) * 10 / self.count
+            };
+            if self.last_printed_step != Some(step) {
+                self.last_printed_step = Some(step);
+                self.write(&format!("{}\n", message));
+            }
+        }
     }
 
     fn inc(&mut self, n: u64) {
         self.pos += n;
         self.print();
     }
+
+    fn finish(&mut self) {
+        if self.is_finished {
+            return;
+        }
+        self.is_finished = true;
+        let duration = format_duration(self.start_time.elapsed().as_secs());
+        let message = if self.is_spinner {
+            format!("{}: done [in {}]\n", self.message, duration)
+        } else {
+            format!(
+                "{}: {} / {} [done in {}]\n",
+                self.message, self.pos, self.count, duration
+            )
+        };
+        if let ProgressBarTarget::Shared { display, line_id } = &self.target {
+            // lines of finished children are removed in terminal mode
+            let permanent_text = if self.parent.is_some() && self.is_terminal {
+                None
+            } else {
+                Some(message.as_str())
+            };
+            lock_ignoring_poison(display).remove_line(*line_id, permanent_text);
+        } else {
+            if self.is_terminal {
+                self.clear_line();
+            }
+            self.write(&message);
+        }
+        if let Some(parent) = self.parent.take() {
+            lock_ignoring_poison(&parent.0).inc(1);
+        }
+    }
+}
+
+impl Drop for ProgressBarInner {
+    fn drop(&mut self) {
+        if !self.is_finished {
+            self.finish();
+        }
+    }
 }